@@ -1,7 +1,8 @@
+use inline_colorization::*;
 use rand::Rng;
 
-use crate::parser::{Literals, Node};
-use std::{cell::RefCell, collections::HashMap, mem::discriminant, ops::Deref, path::PathBuf, rc::Rc, usize};
+use crate::{parser::{Literals, Node, BUILTINS}, parse_source, symbol::Symbol, AsplError};
+use std::{cell::RefCell, collections::HashMap, env, fmt, fs, hash::{Hash, Hasher}, io::{self, BufRead, IsTerminal, Write}, iter::Peekable, mem::discriminant, ops::{Deref, DerefMut}, path::{Path, PathBuf}, process, rc::Rc, str::Chars, sync::Arc, time::{Duration, Instant}, usize};
 
 macro_rules! compare {
     ($left:expr, $condition:expr, $right:expr) => {
@@ -17,46 +18,307 @@ macro_rules! compare {
     };
 }
 
+// Resolves a path relative to cwd into a canonical absolute path
+fn absolute_path(cwd: &Path, path: &str) -> io::Result<PathBuf> {
+    cwd.join(path).canonicalize()
+}
+
+// Levenshtein distance, used to suggest builtins on typos
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut row: Vec<usize> = (0..=right.len()).collect();
+
+    for (i, left_char) in left.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, right_char) in right.iter().enumerate() {
+            let substitution = diagonal + (left_char != *right_char) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[right.len()]
+}
+
+// Negative indexes count back from the end, like arr[-1]
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+
+    match index >= 0 && (index as usize) < len {
+        true    => Some(index as usize),
+        false   => None
+    }
+}
+
+// Writes a value the way load_state reads it back,
+// None for values like functions that can't be written
+fn dump_value(value: &Values) -> Option<String> {
+    match value {
+        Values::Integer(integer)    => Some(integer.to_string()),
+        // Newlines are escaped too, since load_state reads one var per line
+        Values::String(str)         => Some(format!(
+            "\"{}\"",
+            str.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        )),
+        Values::Boolean(boolean)    => Some(boolean.to_string()),
+        Values::None                => Some("none".to_string()),
+        Values::Array(values)       => values.iter()
+            .map(dump_value)
+            .collect::<Option<Vec<String>>>()
+            .map(|values| format!("[{}]", values.join(" "))),
+        _ => None
+    }
+}
+
+fn load_value(chars: &mut Peekable<Chars>) -> Result<Values, String> {
+    while chars.next_if(|char| char.is_whitespace()).is_some() {}
+
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+
+            let mut str = String::new();
+
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Values::String(str)),
+                    Some('\\') => match chars.next() {
+                        Some('n') => str.push('\n'),
+                        Some('r') => str.push('\r'),
+                        Some(char) => str.push(char),
+                        None => return Err("Unterminated string".to_string())
+                    },
+                    Some(char) => str.push(char),
+                    None => return Err("Unterminated string".to_string())
+                }
+            }
+        },
+        Some('[') => {
+            chars.next();
+
+            let mut values: Vec<Values> = vec![];
+
+            loop {
+                while chars.next_if(|char| char.is_whitespace()).is_some() {}
+
+                match chars.peek() {
+                    Some(']') => {
+                        chars.next();
+                        return Ok(Values::Array(Rc::new(values)));
+                    },
+                    Some(_) => values.push(load_value(chars)?),
+                    None => return Err("Unterminated array".to_string())
+                }
+            }
+        },
+        Some(_) => {
+            let mut word = String::new();
+
+            while let Some(char) = chars.next_if(|char| !char.is_whitespace() && *char != ']') {
+                word.push(char);
+            }
+
+            match word.as_str() {
+                "true"  => Ok(Values::Boolean(true)),
+                "false" => Ok(Values::Boolean(false)),
+                "none"  => Ok(Values::None),
+                word    => word.parse()
+                    .map(Values::Integer)
+                    .map_err(|_| format!("Unknown value {:?}", word))
+            }
+        },
+        None => Err("Missing value".to_string())
+    }
+}
+
 #[derive(Debug)]
 pub enum ErrorTypes {
     IndexOutOfBounds,
+    IoError,
     MathError,
+    ParseError,
     UnknownError,
     TypeError,
     UndefinedVar,
     UndefinedFn,
+    ValidationError,
+    FuelExhausted,
+    TimeoutError,
+    ImportError,
+    UserError,
 }
 
 #[derive(Debug)]
 pub struct InterpreterError {
     pub r#type: ErrorTypes,
     pub message: String,
+    // Line and col of the innermost statement that was running
+    pub position: Option<(usize, usize)>,
 }
 
-type InterpreterResult<T> = Result<T, InterpreterError>;
+pub type InterpreterResult<T> = Result<T, InterpreterError>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Values {
     Integer(i64),
+    BigInt(BigInt),
     String(String),
     Boolean(bool),
-    Array(Vec<Values>),
+    // Shared so reading an array out of a variable doesn't copy it,
+    // builtins that need their own copy take it with Rc::unwrap_or_clone
+    Array(Rc<Vec<Values>>),
+    // Shared like arrays, so a function can be looked up or
+    // bound for recursion without copying its body
     Function {
         identifier: String,
-        args: Vec<Box<Node>>,
-        scope: Box<Node>,
+        args: Rc<Vec<Box<Node>>>,
+        scope: Rc<Node>,
     },
+    Namespace(HashMap<Symbol, Values>),
+    Record(HashMap<Symbol, Values>),
     None,
     Break
 }
 
+// Only what is_hashable accepts is hashed by content, anything
+// else hashes by its kind alone, which still agrees with Eq
+impl Hash for Values {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+
+        match self {
+            Values::Integer(integer)    => integer.hash(state),
+            Values::BigInt(bigint)      => bigint.hash(state),
+            Values::String(str)         => str.hash(state),
+            Values::Boolean(boolean)    => boolean.hash(state),
+            Values::Array(values)       => values.hash(state),
+            _ => ()
+        }
+    }
+}
+
+// Non negative integer too big for an i64, kept as
+// base 1e9 limbs with the least significant first
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BigInt(Vec<u32>);
+
+impl BigInt {
+    const BASE: u64 = 1_000_000_000;
+
+    fn from_u64(mut value: u64) -> Self {
+        let mut limbs = vec![(value % Self::BASE) as u32];
+        value /= Self::BASE;
+
+        while value > 0 {
+            limbs.push((value % Self::BASE) as u32);
+            value /= Self::BASE;
+        }
+
+        BigInt(limbs)
+    }
+
+    fn mul_small(&mut self, factor: u64) {
+        let mut carry: u128 = 0;
+
+        for limb in self.0.iter_mut() {
+            let product = *limb as u128 * factor as u128 + carry;
+            *limb = (product % Self::BASE as u128) as u32;
+            carry = product / Self::BASE as u128;
+        }
+
+        while carry > 0 {
+            self.0.push((carry % Self::BASE as u128) as u32);
+            carry /= Self::BASE as u128;
+        }
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        self.0.iter().rev().try_fold(0i64, |acc, limb| {
+            acc.checked_mul(Self::BASE as i64)?.checked_add(*limb as i64)
+        })
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut limbs = self.0.iter().rev();
+
+        if let Some(limb) = limbs.next() {
+            write!(f, "{}", limb)?;
+        }
+
+        for limb in limbs {
+            write!(f, "{:09}", limb)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Values {
     fn is_none(&self)   -> bool { matches!(self, Values::None) }
     fn is_break(&self)  -> bool { matches!(self, Values::Break) }
 
+    // Values that can key a memoized function's cache
+    fn is_hashable(&self) -> bool {
+        match self {
+            Values::Integer(_)  |
+            Values::BigInt(_)   |
+            Values::String(_)   |
+            Values::Boolean(_)  |
+            Values::None        => true,
+            Values::Array(values) => values.iter().all(Values::is_hashable),
+            _ => false
+        }
+    }
+
+    // Non zero ints, non empty strings and arrays and true are truthy,
+    // None for values like functions that are neither
+    fn truthy(&self) -> Option<bool> {
+        match self {
+            Values::Integer(integer)    => Some(*integer != 0),
+            Values::BigInt(_)           => Some(true),
+            Values::String(str)         => Some(!str.is_empty()),
+            Values::Boolean(boolean)    => Some(*boolean),
+            Values::Array(values)       => Some(!values.is_empty()),
+            Values::Record(fields)      => Some(!fields.is_empty()),
+            Values::None                => Some(false),
+            _                           => None
+        }
+    }
+
+    // How log prints a value, unlike name strings aren't quoted,
+    // also not when they're inside an array like [a, b, c]
+    fn display(&self) -> String {
+        match self {
+            Values::String(str)     => str.clone(),
+            Values::Array(values)   => format!("[{}]", values.iter().map(Values::display).collect::<Vec<String>>().join(", ")),
+            // Sorted so the same record always logs the same way
+            Values::Record(fields)  => {
+                let mut fields: Vec<(Arc<str>, &Values)> = fields.iter()
+                    .map(|(field, value)| (field.name(), value))
+                    .collect();
+
+                fields.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+                format!("{{{}}}", fields.iter()
+                    .map(|(field, value)| format!("{}: {}", field, value.display()))
+                    .collect::<Vec<String>>()
+                    .join(", "))
+            },
+            value                   => value.name()
+        }
+    }
+
     fn name(&self) -> String {
         match self {
             Values::Integer(integer)    => integer.to_string(),
+            Values::BigInt(bigint)      => bigint.to_string(),
             Values::String(str)         => format!("{:?}", str),
             Values::Boolean(boolean)    => boolean.to_string(),
             Values::Array(values)       => format!("{:?}", values),
@@ -64,7 +326,9 @@ impl Values {
                 identifier,
                 ..
             }                           => identifier.to_string(),
-            Values::None                => "None".to_string(),
+            Values::Namespace(_)        => "namespace".to_string(),
+            Values::Record(_)           => "record".to_string(),
+            Values::None                => "none".to_string(),
             Values::Break               => "Break".to_string(),
         }
     }
@@ -72,26 +336,24 @@ impl Values {
 
 #[derive(Debug)]
 struct Env {
-    vars: HashMap<String, Values>,
-    parent: Option<Rc<RefCell<Env>>>,
-    cwd: PathBuf
+    vars: HashMap<Symbol, Values>,
+    parent: Option<Rc<RefCell<Env>>>
 }
 
 impl Env {
-    fn new(parent: Option<Rc<RefCell<Env>>>, cwd: PathBuf) -> Self {
+    fn new(parent: Option<Rc<RefCell<Env>>>) -> Self {
         Env {
             vars: HashMap::new(),
-            parent,
-            cwd
+            parent
         }
     }
 
-    fn set(&mut self, name: &str, value: Values) {
-        self.vars.insert(name.to_string(), value);
+    fn set(&mut self, name: Symbol, value: Values) {
+        self.vars.insert(name, value);
     }
 
-    fn update(&mut self, name: &str, value: Values) -> InterpreterResult<Values> {
-        if let Some(var) = self.vars.get_mut(name) {
+    fn update(&mut self, name: Symbol, value: Values) -> InterpreterResult<Values> {
+        if let Some(var) = self.vars.get_mut(&name) {
             *var = value.clone();
             return Ok(value.clone())
         }
@@ -103,37 +365,263 @@ impl Env {
         Err(InterpreterError {
             r#type: ErrorTypes::UndefinedVar,
             message: format!("Cannot find var: {:?}", name),
+            position: None,
         })
     }
 
-    fn get(&self, name: &str) -> InterpreterResult<Values> {
-        if let Some(value) = self.vars.get(name) {
+    fn update_global(&mut self, name: Symbol, value: Values) -> InterpreterResult<Values> {
+        match self.parent {
+            Some(ref parent) => parent.borrow_mut().update_global(name, value),
+            None => self.update(name, value)
+        }
+    }
+
+    fn get_global(&self, name: Symbol) -> InterpreterResult<Values> {
+        match self.parent {
+            Some(ref parent) => parent.borrow().get_global(name),
+            None => self.get(name)
+        }
+    }
+
+    fn get(&self, name: Symbol) -> InterpreterResult<Values> {
+        if let Some(value) = self.vars.get(&name) {
             return Ok(value.clone());
         }
 
         if let Some(ref parent) = self.parent {
-            return parent.borrow().get(name);
+            if let Ok(value) = parent.borrow().get(name) {
+                return Ok(value);
+            }
+        }
+
+        // Qualified names like lib.greet look inside the lib namespace,
+        // and person.name reads the name field of the person record
+        if let Some((namespace, member)) = name.name().split_once('.') {
+            if let Ok(Values::Namespace(vars) | Values::Record(vars)) = self.get(Symbol::intern(namespace)) {
+                let mut namespace_env = Env::new(None);
+                namespace_env.vars = vars;
+
+                if let Ok(value) = namespace_env.get(Symbol::intern(member)) {
+                    return Ok(value);
+                }
+            }
         }
 
         Err(InterpreterError {
             r#type: ErrorTypes::UndefinedVar,
             message: format!("Cannot find var: {:?}", name),
+            position: None,
         })
     }
 }
 
+// Cached results of a function marked with @memoize, by its args.
+// Memos are keyed by the address of the function's body, which is held
+// here so no other function can end up at that address while it's cached.
+struct Memo {
+    _scope: Rc<Node>,
+    results: HashMap<Vec<Values>, Values>,
+}
+
+// Swaps in a new env for the interpreter and puts the previous
+// one back when dropped, so early returns and errors can't leak it
+struct EnvGuard<'a> {
+    interpreter: &'a mut Interpreter,
+    prev_env: Option<Rc<RefCell<Env>>>,
+}
+
+impl<'a> EnvGuard<'a> {
+    fn new(interpreter: &'a mut Interpreter, env: Rc<RefCell<Env>>) -> Self {
+        let prev_env = std::mem::replace(&mut interpreter.env, env);
+
+        Self {
+            interpreter,
+            prev_env: Some(prev_env),
+        }
+    }
+}
+
+impl Deref for EnvGuard<'_> {
+    type Target = Interpreter;
+
+    fn deref(&self) -> &Interpreter {
+        self.interpreter
+    }
+}
+
+impl DerefMut for EnvGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Interpreter {
+        self.interpreter
+    }
+}
+
+impl Drop for EnvGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(prev_env) = self.prev_env.take() {
+            self.interpreter.env = prev_env;
+        }
+    }
+}
+
 pub struct Interpreter {
     env: Rc<RefCell<Env>>,
+    // Held here once rather than copied into every scope's env
+    cwd: PathBuf,
+    source_dir: PathBuf,
+    args: Vec<String>,
+    bool_math: bool,
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    steps: u32,
+    output: Box<dyn Write>,
+    // None reads from stdin
+    input: Option<Box<dyn BufRead>>,
+    imports: HashMap<PathBuf, HashMap<Symbol, Values>>,
+    // Files still being sourced, to catch circular imports
+    importing: Vec<PathBuf>,
+    // Names of the functions being called, outermost first
+    call_stack: Vec<String>,
+    // The call stack where the last error happened, innermost first
+    backtrace: Vec<String>,
+    // Functions marked with @memoize, by the address of their body, so
+    // two functions that share a name don't share a cache
+    memos: HashMap<*const Node, Memo>,
 }
 
 impl Interpreter {
+    // cwd is where the script's paths resolve from, main
+    // passes the script's parent so they're relative to the script
     pub fn new(cwd: PathBuf) -> Self {
+        let source_dir = cwd.clone();
+
         Self {
-            env: Rc::new(RefCell::new(Env::new(None, cwd))),
+            env: Rc::new(RefCell::new(Env::new(None))),
+            cwd,
+            source_dir,
+            args: vec![],
+            bool_math: false,
+            fuel: None,
+            deadline: None,
+            steps: 0,
+            output: Box::new(io::stdout()),
+            input: None,
+            imports: HashMap::new(),
+            importing: vec![],
+            call_stack: vec![],
+            backtrace: vec![],
+            memos: HashMap::new(),
+        }
+    }
+
+    // Sends log output to writer instead of stdout, so tests
+    // and host programs can collect what a script prints
+    pub fn with_output(cwd: PathBuf, output: Box<dyn Write>) -> Self {
+        let mut interpreter = Self::new(cwd);
+        interpreter.output = output;
+
+        interpreter
+    }
+
+    // Reads input from reader instead of stdin, so interactive
+    // scripts can be driven with canned input
+    pub fn with_input(cwd: PathBuf, input: Box<dyn BufRead>) -> Self {
+        let mut interpreter = Self::new(cwd);
+        interpreter.input = Some(input);
+
+        interpreter
+    }
+
+    // None once the input has run out
+    fn read_line(&mut self) -> InterpreterResult<Option<String>> {
+        let mut line = String::new();
+
+        let read = match self.input {
+            Some(ref mut input) => input.read_line(&mut line),
+            None => io::stdin().read_line(&mut line)
+        };
+
+        match read {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+            Err(err) => Err(InterpreterError {
+                r#type: ErrorTypes::IoError,
+                message: format!("Cannot read input: {}", err),
+                position: None
+            })
+        }
+    }
+
+    fn write_output(&mut self, text: &str) -> InterpreterResult<()> {
+        let written = self.output.write_all(text.as_bytes())
+            .and_then(|_| self.output.flush());
+
+        match written {
+            Ok(()) => Ok(()),
+            Err(err) => Err(InterpreterError {
+                r#type: ErrorTypes::IoError,
+                message: format!("Cannot write output: {}", err),
+                position: None
+            })
+        }
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    // Off by default, math on booleans is usually a mistake.
+    // When on, @math treats true as 1 and false as 0.
+    pub fn set_bool_math(&mut self, bool_math: bool) {
+        self.bool_math = bool_math;
+    }
+
+    // Caps how many statements and loop iterations can run,
+    // so untrusted scripts can't run forever
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    fn consume_fuel(&mut self) -> InterpreterResult<()> {
+        match self.fuel {
+            Some(0) => Err(InterpreterError {
+                r#type: ErrorTypes::FuelExhausted,
+                message: format!("Ran out of steps"),
+                position: None,
+            }),
+            Some(ref mut fuel) => {
+                *fuel -= 1;
+                Ok(())
+            },
+            None => Ok(())
+        }
+    }
+
+    // Starts counting from now, so call it right before running
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline = timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    // Reading the clock on every step would slow down tight loops,
+    // so the deadline is only checked once every 1024 steps
+    fn check_timeout(&mut self) -> InterpreterResult<()> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok(())
+        };
+
+        self.steps = self.steps.wrapping_add(1);
+        if !self.steps.is_multiple_of(1024) || Instant::now() < deadline {
+            return Ok(());
         }
+
+        Err(InterpreterError {
+            r#type: ErrorTypes::TimeoutError,
+            message: format!("Ran out of time"),
+            position: None,
+        })
     }
 
-    fn handle_fn(&mut self, identifier: &Box<Node>, args: &Vec<Box<Node>>, scope: &Box<Node>) -> InterpreterResult<Values> {
+    fn handle_fn(&mut self, identifier: &Box<Node>, args: &[Box<Node>], scope: &Box<Node>) -> InterpreterResult<Values> {
         let identifier = match identifier.deref() {
             Node::Identifier(identifier) => identifier,
             _ => unreachable!(),
@@ -141,11 +629,11 @@ impl Interpreter {
 
         let function = Values::Function {
             identifier: identifier.to_string(),
-            args: args.clone(),
-            scope: scope.clone(),
+            args: Rc::new(args.to_vec()),
+            scope: Rc::new(scope.deref().clone()),
         };
 
-        self.env.borrow_mut().set(identifier.as_str(), function);
+        self.env.borrow_mut().set(*identifier, function);
 
         Ok(Values::None)
     }
@@ -156,88 +644,268 @@ impl Interpreter {
     }
 
     fn handle_fn_call(&mut self, identifier: &Box<Node>, args: &Vec<Box<Node>>) -> InterpreterResult<Values> {
-        let name = match identifier.deref() {
-            Node::Identifier(identifier) => identifier,
-            _ => unreachable!(),
+        let (name, function) = match identifier.deref() {
+            Node::Identifier(identifier) => (identifier.to_string(), self.env.borrow().get(*identifier)),
+            // A callee like handlers[0] is evaluated to get the function
+            callee => match self.handle_value(callee)? {
+                Values::Function { identifier, args, scope } => (identifier.clone(), Ok(Values::Function { identifier, args, scope })),
+                value => return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot call {:?}, it is not a function", value.name()),
+                    position: None,
+                })
+            }
         };
 
-        let (fn_args, fn_scope) = match self.env.borrow().get(name.as_str()) {
-            Ok(Values::Function { args, scope, .. }) => (args, scope),
+        let function = match function {
+            Ok(function @ Values::Function { .. }) => function,
             _ => {
+                let suggestion = BUILTINS.iter()
+                    .map(|builtin| (edit_distance(&name, builtin), builtin))
+                    .filter(|(distance, builtin)| *distance * 3 <= builtin.len())
+                    .min_by_key(|(distance, _)| *distance);
+
+                if let Some((_, builtin)) = suggestion {
+                    return Err(InterpreterError {
+                        r#type: ErrorTypes::UndefinedFn,
+                        message: format!("Cannot find function: {:?} (did you mean @{})", name, builtin),
+                        position: None,
+                    })
+                }
+
                 return Err(InterpreterError {
                     r#type: ErrorTypes::UndefinedFn,
                     message: format!("Cannot find function: {:?}", name),
+                    position: None,
                 })
             }
         };
 
-        if args.len() != fn_args.len() {
+        let mut values: Vec<Values> = vec![];
+
+        for arg in args {
+            values.push(self.handle_value(arg.deref())?);
+        }
+
+        self.call_function(&name, function, values)
+    }
+
+    // Calls a function value with args that are already evaluated,
+    // name is what the caller called it, used in error messages
+    fn call_function(&mut self, name: &str, function: Values, values: Vec<Values>) -> InterpreterResult<Values> {
+        // The declared name, which is what shows up in backtraces
+        // even when the function was passed in under another name
+        let (fn_name, fn_args, fn_scope) = match function.clone() {
+            Values::Function { identifier, args, scope } => (identifier, args, scope),
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot call {:?}, it is not a function", value.name()),
+                position: None,
+            })
+        };
+
+        let required = fn_args.iter()
+            .filter(|fn_arg| matches!(fn_arg.as_ref(), Node::Identifier(_)))
+            .count();
+
+        let variadic = fn_args.last().is_some_and(|fn_arg| matches!(fn_arg.as_ref(), Node::RestArg(_)));
+
+        if values.len() < required || (!variadic && values.len() > fn_args.len()) {
+            let expected = match (variadic, required == fn_args.len()) {
+                (true, _)       => format!("at least {}", required),
+                (false, true)   => required.to_string(),
+                (false, false)  => format!("{} to {}", required, fn_args.len())
+            };
+
             return Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
                 message: format!(
                     "Argument mismatch on function {:?}, Expected {} but found only {}",
                     name,
-                    fn_args.len(),
-                    args.len()
+                    expected,
+                    values.len()
                 ),
+                position: None,
             });
         }
 
-        let fn_env = Rc::new(RefCell::new(
-            Env::new(
-                None,
-                self.env.borrow().cwd.clone()
-            )
-        ));
+        // A memoized function answers args it has seen before from its cache
+        let memo_key = match self.memos.get(&Rc::as_ptr(&fn_scope)) {
+            Some(memo) if values.iter().all(Values::is_hashable) => {
+                if let Some(value) = memo.results.get(&values) {
+                    return Ok(value.clone());
+                }
+
+                Some(values.clone())
+            },
+            _ => None
+        };
+
+        // The function can see itself under its declared name, so it can recurse
+        let fn_env = Rc::new(RefCell::new(Env::new(None)));
+        fn_env.borrow_mut().set(Symbol::intern(&fn_name), function);
+
+        let mut values = values.into_iter();
+
+        for fn_arg in fn_args.iter() {
+            // Whatever wasn't taken by the args before it
+            if let Node::RestArg(identifier) = fn_arg.deref() {
+                if let Node::Identifier(fn_arg) = identifier.deref() {
+                    fn_env.borrow_mut().set(*fn_arg, Values::Array(Rc::new(values.by_ref().collect())));
+                }
 
-        for (fn_arg, arg) in fn_args.deref().into_iter().zip(args.deref().into_iter()) {
-            if let Node::Identifier(fn_arg) = fn_arg.deref() {
-                let val = self.handle_value(arg.deref())?;
-                fn_env.borrow_mut().set(fn_arg, val);
+                break;
             }
+
+            // Left out trailing args take their default
+            let (fn_arg, val) = match (fn_arg.deref(), values.next()) {
+                (Node::Identifier(fn_arg), Some(val))               => (fn_arg, val),
+                (Node::DefaultArg { identifier, default }, val)     => match identifier.deref() {
+                    Node::Identifier(fn_arg) => match val {
+                        Some(val)   => (fn_arg, val),
+                        None        => (fn_arg, self.handle_value(default.deref())?)
+                    },
+                    _ => unreachable!()
+                },
+                _ => unreachable!()
+            };
+
+            fn_env.borrow_mut().set(*fn_arg, val);
         }
 
-        let prev_env = std::mem::replace(&mut self.env, fn_env);
+        let mut guard = EnvGuard::new(self, fn_env);
+        guard.call_stack.push(fn_name.clone());
+
+        let mut result = Ok(Values::None);
 
         if let Node::Scope { body } = fn_scope.deref() {
             for scope_node in body {
-                let ret_value = self.exec_node(scope_node.deref())?;
-                if !ret_value.is_none() {
-                    self.env = prev_env;
-                    return Ok(ret_value);
+                match guard.exec_node(scope_node.deref()) {
+                    Ok(ret_value) if ret_value.is_none() => (),
+                    ret_value => {
+                        result = ret_value;
+                        break;
+                    }
                 }
             }
         }
 
-        self.env = prev_env;
+        // Only the innermost call sees the stack as it was when the error happened
+        if result.is_err() && guard.backtrace.is_empty() {
+            guard.backtrace = guard.call_stack.iter().rev().cloned().collect();
+        }
 
-        Ok(Values::None)
+        guard.call_stack.pop();
+
+        if let (Some(key), Ok(value)) = (memo_key, &result) {
+            if let Some(memo) = guard.memos.get_mut(&Rc::as_ptr(&fn_scope)) {
+                memo.results.insert(key, value.clone());
+            }
+        }
+
+        result
     }
 
-    fn handle_source(&mut self, _file_name: &String, _cwd: &PathBuf, ast: &Vec<Node>) -> InterpreterResult<Values> {
-        for node in ast {
-            self.exec_node(node)?;
+    // Reads, parses and runs the sourced file in its own env once,
+    // then merges its top level definitions into the current env.
+    // Sourcing the same file again only merges the definitions.
+    // Paths resolve relative to the file doing the sourcing.
+    // With an alias the definitions are kept in a namespace instead.
+    fn handle_source(&mut self, file_name: &String, alias: &Option<Symbol>) -> InterpreterResult<Values> {
+        let path = match absolute_path(&self.source_dir, file_name) {
+            Ok(path) => path,
+            Err(err) => return Err(InterpreterError {
+                r#type: ErrorTypes::IoError,
+                message: format!("Cannot resolve source path {:?}: {}", file_name, err),
+                position: None
+            })
+        };
+
+        if self.importing.contains(&path) {
+            let chain = self.importing
+                .iter()
+                .chain([&path])
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            return Err(InterpreterError {
+                r#type: ErrorTypes::ImportError,
+                message: format!("circular import: {}", chain),
+                position: None
+            });
+        }
+
+        if !self.imports.contains_key(&path) {
+            let source = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(err) => return Err(InterpreterError {
+                    r#type: ErrorTypes::IoError,
+                    message: format!("Cannot read file {:?}: {}", path, err),
+                    position: None
+                })
+            };
+
+            let ast = match parse_source(&source) {
+                Ok(ast) => ast,
+                Err(AsplError::Lexer(err)) => return Err(InterpreterError {
+                    r#type: ErrorTypes::ParseError,
+                    message: format!("Lexing Error in {:?}: {}", path, err.message),
+                    position: None
+                }),
+                Err(err) => return Err(InterpreterError {
+                    r#type: ErrorTypes::ParseError,
+                    message: format!("Parsing Error in {:?}: {}", path, err.message()),
+                    position: None
+                })
+            };
+
+            let source_env = Rc::new(RefCell::new(Env::new(None)));
+
+            let prev_env = std::mem::replace(&mut self.env, source_env);
+            let prev_source_dir = std::mem::replace(
+                &mut self.source_dir,
+                path.parent().map(Path::to_path_buf).unwrap_or_default()
+            );
+
+            self.importing.push(path.clone());
+            let result = self.run(&ast);
+            self.importing.pop();
+
+            let source_env = std::mem::replace(&mut self.env, prev_env);
+            self.source_dir = prev_source_dir;
+
+            // Positions in the sourced file would be read as positions in this one
+            result.map_err(|err| InterpreterError { position: None, ..err })?;
+
+            let vars = source_env.borrow().vars.clone();
+            self.imports.insert(path.clone(), vars);
+        }
+
+        // Aliased imports live under their own namespace, as in lib.greet
+        if let Some(alias) = alias {
+            let namespace = Values::Namespace(self.imports[&path].clone());
+            self.env.borrow_mut().set(*alias, namespace);
+
+            return Ok(Values::None);
+        }
+
+        for (name, value) in &self.imports[&path] {
+            self.env.borrow_mut().set(*name, value.clone());
         }
 
         Ok(Values::None)
     }
 
     fn handle_scope(&mut self, body: &Vec<Box<Node>>) -> InterpreterResult<Values> {
-        let new_env = Rc::new(RefCell::new(
-            Env::new(
-                Some(self.env.clone()),
-                self.env.borrow().cwd.clone()
-            )
-        ));
+        let new_env = Rc::new(RefCell::new(Env::new(Some(self.env.clone()))));
 
-        let prev_env = std::mem::replace(&mut self.env, new_env);
+        let mut guard = EnvGuard::new(self, new_env);
 
         for scope_node in body {
-            self.exec_node(scope_node.deref())?;
+            guard.exec_node(scope_node.deref())?;
         }
 
-        self.env = prev_env;
-
         Ok(Values::None)
     }
 
@@ -245,19 +913,23 @@ impl Interpreter {
         let left_value = match left.deref() {
             Node::Literal(literal) => match literal {
                 Literals::Int(integer) => integer.clone(),
+                Literals::Boolean(boolean) if self.bool_math => *boolean as i64,
                 _ => return Err(InterpreterError {
                     r#type: ErrorTypes::TypeError,
-                    message: format!("Cannot do math on {:?}", literal.name())
+                    message: format!("Cannot do math on {:?}", literal.name()),
+                    position: None
                 })
             },
             Node::Identifier(identifier) => {
-                let variable = self.env.borrow().get(identifier.as_str())?;
+                let variable = self.env.borrow().get(*identifier)?;
 
                 match variable {
                     Values::Integer(integer) => integer,
+                    Values::Boolean(boolean) if self.bool_math => boolean as i64,
                     _ => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Cannot do math on {:?}", variable)
+                        message: format!("Cannot do math on {:?}", variable),
+                        position: None
                     })
                 }
             },
@@ -268,31 +940,50 @@ impl Interpreter {
                     _ => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
                         message: format!("Cannot do math on {:?}", nested_result),
+                        position: None,
                     }),
                 }
             },
+            Node::ArrayAccess { identifier, index } => {
+                let element = self.handle_array_access(identifier, index)?;
+
+                match element {
+                    Values::Integer(integer) => integer,
+                    Values::Boolean(boolean) if self.bool_math => boolean as i64,
+                    _ => return Err(InterpreterError {
+                        r#type: ErrorTypes::TypeError,
+                        message: format!("Cannot do math on {:?}", element),
+                        position: None
+                    })
+                }
+            },
             _ => return Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
-                message: format!("Cannot do math on {:?}", left)
+                message: format!("Cannot do math on {:?}", left),
+                position: None
             })
         };
 
         let right_value = match right.deref() {
             Node::Literal(literal) => match literal {
                 Literals::Int(integer) => integer.clone(),
+                Literals::Boolean(boolean) if self.bool_math => *boolean as i64,
                 _ => return Err(InterpreterError {
                     r#type: ErrorTypes::TypeError,
-                    message: format!("Cannot do math on {:?}", literal.name())
+                    message: format!("Cannot do math on {:?}", literal.name()),
+                    position: None
                 })
             },
             Node::Identifier(identifier) => {
-                let variable = self.env.borrow().get(identifier.as_str())?;
+                let variable = self.env.borrow().get(*identifier)?;
 
                 match variable {
                     Values::Integer(integer) => integer,
+                    Values::Boolean(boolean) if self.bool_math => boolean as i64,
                     _ => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Cannot do math on {:?}", variable)
+                        message: format!("Cannot do math on {:?}", variable),
+                        position: None
                     })
                 }
             },
@@ -303,12 +994,27 @@ impl Interpreter {
                     _ => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
                         message: format!("Cannot do math on {:?}", nested_result),
+                        position: None,
                     }),
                 }
             },
+            Node::ArrayAccess { identifier, index } => {
+                let element = self.handle_array_access(identifier, index)?;
+
+                match element {
+                    Values::Integer(integer) => integer,
+                    Values::Boolean(boolean) if self.bool_math => boolean as i64,
+                    _ => return Err(InterpreterError {
+                        r#type: ErrorTypes::TypeError,
+                        message: format!("Cannot do math on {:?}", element),
+                        position: None
+                    })
+                }
+            },
             _ => return Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
-                message: format!("Cannot do math on {:?}", left)
+                message: format!("Cannot do math on {:?}", left),
+                position: None
             })
         };
 
@@ -321,6 +1027,7 @@ impl Interpreter {
                     return Err(InterpreterError {
                         r#type: ErrorTypes::MathError,
                         message: "Division by zero".to_string(),
+                        position: None,
                     })
                 }
 
@@ -329,16 +1036,54 @@ impl Interpreter {
             _ => Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
                 message: format!("Unknown operator: {}", op),
+                position: None,
             }),
         }
     }
 
+    fn handle_record(&mut self, fields: &Vec<(Node, Node)>) -> InterpreterResult<Values> {
+        let mut record: HashMap<Symbol, Values> = HashMap::new();
+
+        for (field, value) in fields {
+            let field = self.handle_str(field, "record")?;
+            let value = self.handle_value(value)?;
+
+            record.insert(Symbol::intern(&field), value);
+        }
+
+        Ok(Values::Record(record))
+    }
+
+    fn handle_choice(&mut self, array: &Box<Node>) -> InterpreterResult<Values> {
+        let values = match self.handle_value(array.deref())? {
+            Values::Array(values) => values,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected an array on @choice, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        if values.is_empty() {
+            return Err(InterpreterError {
+                r#type: ErrorTypes::IndexOutOfBounds,
+                message: format!("Cannot @choice from an empty array"),
+                position: None
+            });
+        }
+
+        let index = rand::thread_rng().gen_range(0..values.len());
+
+        Ok(values[index].clone())
+    }
+
     fn handle_random(&mut self, start: &Box<Node>, end: &Box<Node>) -> InterpreterResult<Values> {
         let start = match self.handle_value(start.deref())? {
             Values::Integer(start) => start,
             value => return Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
-                message: format!("Cannot generate a random number based on {:?}", value.name())
+                message: format!("Cannot generate a random number based on {:?}", value.name()),
+                position: None
             })
         };
 
@@ -346,7 +1091,8 @@ impl Interpreter {
             Values::Integer(end) => end,
             value => return Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
-                message: format!("Cannot generate a random number based on {:?}", value.name())
+                message: format!("Cannot generate a random number based on {:?}", value.name()),
+                position: None
             })
         };
 
@@ -356,14 +1102,1137 @@ impl Interpreter {
         Ok(Values::Integer(generated))
     }
 
-    fn handle_var(&mut self, identifier: &Box<Node>, value: &Box<Node>) -> InterpreterResult<Values> {
-        let name = match identifier.deref() {
-            Node::Identifier(identifier) => identifier,
-            _ => unreachable!(),
-        };
+    fn handle_args(&mut self) -> InterpreterResult<Values> {
+        let args = self.args.iter()
+            .map(|arg| Values::String(arg.clone()))
+            .collect();
+
+        Ok(Values::Array(Rc::new(args)))
+    }
+
+    fn handle_arg(&mut self, index: &Box<Node>) -> InterpreterResult<Values> {
+        let index = match self.handle_value(index.deref())? {
+            Values::Integer(index) => index,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot access arg with {:?}", value.name()),
+                position: None
+            })
+        };
+
+        match usize::try_from(index).ok().and_then(|index| self.args.get(index)) {
+            Some(arg) => Ok(Values::String(arg.clone())),
+            None => Err(InterpreterError {
+                r#type: ErrorTypes::IndexOutOfBounds,
+                message: format!("Cannot access arg {}, only {} were given", index, self.args.len()),
+                position: None
+            })
+        }
+    }
+
+    // Exits the process right away, so nothing after the
+    // @exit call runs, not even the rest of the enclosing scopes.
+    fn handle_exit(&mut self, code: &Box<Node>) -> InterpreterResult<Values> {
+        let code = match self.handle_value(code.deref())? {
+            Values::Integer(code) => code,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot exit with {:?}", value.name()),
+                position: None
+            })
+        };
+
+        let _ = self.output.flush();
+        process::exit(code as i32);
+    }
+
+    fn handle_raise(&mut self, message: &Box<Node>) -> InterpreterResult<Values> {
+        let message = self.handle_str(message, "error")?;
+
+        Err(InterpreterError {
+            r#type: ErrorTypes::UserError,
+            message,
+            position: None
+        })
+    }
+
+    fn handle_read_key(&mut self) -> InterpreterResult<Values> {
+        let _ = self.output.flush();
+
+        if cfg!(feature = "raw-input") && self.input.is_none() && io::stdin().is_terminal() {
+            return self.read_raw_key();
+        }
+
+        // Not a terminal (or raw input is disabled), read a whole line instead
+        Ok(Values::String(self.read_line()?.unwrap_or_default()))
+    }
+
+    // Asks again on a bad number when someone is typing,
+    // piped or canned input can't be corrected so that's an error
+    fn handle_input_int(&mut self, prompt: &Option<Box<Node>>) -> InterpreterResult<Values> {
+        let prompt = match prompt {
+            Some(prompt) => self.handle_str(prompt, "input_int")?,
+            None => String::new()
+        };
+
+        let interactive = self.input.is_none() && io::stdin().is_terminal();
+
+        loop {
+            self.write_output(&prompt)?;
+
+            let line = match self.read_line()? {
+                Some(line) => line,
+                None => return Err(InterpreterError {
+                    r#type: ErrorTypes::IoError,
+                    message: format!("Input ended before an int was given on @input_int"),
+                    position: None
+                })
+            };
+
+            match line.trim().parse::<i64>() {
+                Ok(integer) => return Ok(Values::Integer(integer)),
+                Err(_) if interactive => continue,
+                Err(_) => return Err(InterpreterError {
+                    r#type: ErrorTypes::ParseError,
+                    message: format!("Cannot parse {:?} as an int on @input_int", line),
+                    position: None
+                })
+            }
+        }
+    }
+
+    #[cfg(feature = "raw-input")]
+    fn read_raw_key(&mut self) -> InterpreterResult<Values> {
+        use crossterm::{event::{self, Event, KeyCode, KeyEventKind}, terminal};
+
+        let io_error = |err: io::Error| InterpreterError {
+            r#type: ErrorTypes::IoError,
+            message: format!("Cannot read key: {}", err),
+            position: None
+        };
+
+        terminal::enable_raw_mode().map_err(io_error)?;
+
+        let key = loop {
+            match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char(char) => break Ok(char.to_string()),
+                    KeyCode::Enter      => break Ok("\n".to_string()),
+                    KeyCode::Tab        => break Ok("\t".to_string()),
+                    KeyCode::Backspace  => break Ok("backspace".to_string()),
+                    KeyCode::Esc        => break Ok("esc".to_string()),
+                    KeyCode::Up         => break Ok("up".to_string()),
+                    KeyCode::Down       => break Ok("down".to_string()),
+                    KeyCode::Left       => break Ok("left".to_string()),
+                    KeyCode::Right      => break Ok("right".to_string()),
+                    _ => continue
+                },
+                Ok(_) => continue,
+                Err(err) => break Err(err)
+            }
+        };
+
+        terminal::disable_raw_mode().map_err(io_error)?;
+
+        Ok(Values::String(key.map_err(io_error)?))
+    }
+
+    #[cfg(not(feature = "raw-input"))]
+    fn read_raw_key(&mut self) -> InterpreterResult<Values> {
+        Err(InterpreterError {
+            r#type: ErrorTypes::IoError,
+            message: format!("Cannot read a single key, aspl was built without the raw-input feature"),
+            position: None
+        })
+    }
+
+    fn handle_color(&mut self, value: &Box<Node>, color: &Box<Node>) -> InterpreterResult<Values> {
+        let value = match self.handle_value(value.deref())? {
+            Values::String(str) => str,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot color {:?}, expected a string", value.name()),
+                position: None
+            })
+        };
+
+        let color_name = match self.handle_value(color.deref())? {
+            Values::String(str) => str,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a color name, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        let color = match color_name.as_str() {
+            "black"     => color_black,
+            "red"       => color_red,
+            "green"     => color_green,
+            "yellow"    => color_yellow,
+            "blue"      => color_blue,
+            "magenta"   => color_magenta,
+            "cyan"      => color_cyan,
+            "white"     => color_white,
+            "reset"     => color_reset,
+            _ => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Unknown color: {:?}", color_name),
+                position: None
+            })
+        };
+
+        // https://no-color.org
+        if env::var("NO_COLOR").is_ok_and(|no_color| !no_color.is_empty()) {
+            return Ok(Values::String(value));
+        }
+
+        Ok(Values::String(format!("{color}{value}{color_reset}")))
+    }
+
+    fn handle_color_log(&mut self, value: &Box<Node>, color: &Box<Node>) -> InterpreterResult<Values> {
+        if let Values::String(colored) = self.handle_color(value, color)? {
+            self.write_output(&format!("{colored}\n"))?;
+        }
+
+        Ok(Values::None)
+    }
+
+    fn handle_format(&mut self, template: &Box<Node>, args: &Vec<Box<Node>>) -> InterpreterResult<Values> {
+        let template = match self.handle_value(template.deref())? {
+            Values::String(str) => str,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a string template on @format, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        let mut values: Vec<String> = vec![];
+
+        for arg in args {
+            let value = match self.handle_value(arg.deref())? {
+                value @ (Values::Function { .. } |
+                         Values::Namespace(_)    |
+                         Values::Break) => return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot format {:?}", value.name()),
+                    position: None
+                }),
+                value => value.display()
+            };
+
+            values.push(value);
+        }
+
+        let mut output = String::new();
+        let mut values = values.into_iter();
+        let mut placeholders = 0;
+        let mut chars = template.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            match (char, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    output.push(char);
+                    chars.next();
+                },
+                ('{', Some('}')) => {
+                    placeholders += 1;
+                    chars.next();
+
+                    if let Some(value) = values.next() {
+                        output.push_str(value.as_str());
+                    }
+                },
+                _ => output.push(char)
+            }
+        }
+
+        if placeholders != args.len() {
+            return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!(
+                    "Argument mismatch on @format, Expected {} but found {}",
+                    placeholders,
+                    args.len()
+                ),
+                position: None
+            });
+        }
+
+        Ok(Values::String(output))
+    }
+
+    fn handle_parse_csv(&mut self, source: &Box<Node>) -> InterpreterResult<Values> {
+        let source = match self.handle_value(source.deref())? {
+            Values::String(str) => str,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot parse {:?} as csv, expected a string", value.name()),
+                position: None
+            })
+        };
+
+        let mut rows: Vec<Values> = vec![];
+        let mut row: Vec<Values> = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = source.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            if quoted {
+                match char {
+                    '"' if chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    },
+                    '"' => quoted = false,
+                    _   => field.push(char)
+                }
+
+                continue;
+            }
+
+            match char {
+                '"' if field.is_empty() => quoted = true,
+                ',' => row.push(Values::String(std::mem::take(&mut field))),
+                '\r' if chars.peek() == Some(&'\n') => continue,
+                '\n' => {
+                    row.push(Values::String(std::mem::take(&mut field)));
+                    rows.push(Values::Array(Rc::new(std::mem::take(&mut row))));
+                },
+                _ => field.push(char)
+            }
+        }
+
+        if quoted {
+            return Err(InterpreterError {
+                r#type: ErrorTypes::ParseError,
+                message: format!("Unterminated quoted field in csv"),
+                position: None
+            });
+        }
+
+        if !field.is_empty() || !row.is_empty() {
+            row.push(Values::String(field));
+            rows.push(Values::Array(Rc::new(row)));
+        }
+
+        Ok(Values::Array(Rc::new(rows)))
+    }
+
+    fn handle_to_csv(&mut self, rows: &Box<Node>) -> InterpreterResult<Values> {
+        let rows = match self.handle_value(rows.deref())? {
+            Values::Array(rows) => Rc::unwrap_or_clone(rows),
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot convert {:?} to csv, expected an array of rows", value.name()),
+                position: None
+            })
+        };
+
+        let mut lines: Vec<String> = vec![];
+
+        for row in rows {
+            let cells = match row {
+                Values::Array(cells) => Rc::unwrap_or_clone(cells),
+                value => return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot convert row {:?} to csv, expected an array", value.name()),
+                    position: None
+                })
+            };
+
+            let mut fields: Vec<String> = vec![];
+
+            for cell in cells {
+                let field = match cell {
+                    Values::Integer(integer)    => integer.to_string(),
+                    Values::String(str)         => str,
+                    Values::Boolean(boolean)    => boolean.to_string(),
+                    value => return Err(InterpreterError {
+                        r#type: ErrorTypes::TypeError,
+                        message: format!("Cannot convert cell {:?} to csv", value.name()),
+                        position: None
+                    })
+                };
+
+                if field.contains([',', '"', '\n', '\r']) {
+                    fields.push(format!("\"{}\"", field.replace('"', "\"\"")));
+                } else {
+                    fields.push(field);
+                }
+            }
+
+            lines.push(fields.join(","));
+        }
+
+        Ok(Values::String(lines.join("\n")))
+    }
+
+    fn handle_abs_path(&mut self, path: &Box<Node>) -> InterpreterResult<Values> {
+        let path = match self.handle_value(path.deref())? {
+            Values::String(str) => str,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a path string on @abs_path, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        match absolute_path(&self.cwd, &path) {
+            Ok(absolute) => Ok(Values::String(absolute.to_string_lossy().to_string())),
+            Err(err) => Err(InterpreterError {
+                r#type: ErrorTypes::IoError,
+                message: format!("Cannot resolve path {:?}: {}", path, err),
+                position: None
+            })
+        }
+    }
+
+    fn handle_path_check(&mut self, path: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let path = self.handle_str(path, builtin)?;
+        let path = self.cwd.join(path);
+
+        match builtin {
+            "is_file"   => Ok(Values::Boolean(path.is_file())),
+            "is_dir"    => Ok(Values::Boolean(path.is_dir())),
+            _           => Ok(Values::Boolean(path.exists()))
+        }
+    }
+
+    // There are no float values yet, so besides ints this
+    // also takes numeric strings like "3.14159"
+    fn handle_fixed(&mut self, value: &Box<Node>, decimals: &Box<Node>) -> InterpreterResult<Values> {
+        let number = match self.handle_value(value.deref())? {
+            Values::Integer(integer) => integer as f64,
+            Values::String(str) => match str.trim().parse::<f64>() {
+                Ok(number) => number,
+                Err(_) => return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot format {:?} as a number", str),
+                    position: None
+                })
+            },
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot format {:?} as a number", value.name()),
+                position: None
+            })
+        };
+
+        let decimals = match self.handle_value(decimals.deref())? {
+            Values::Integer(decimals) if decimals >= 0 => decimals as usize,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a non negative int of decimals on @fixed, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        Ok(Values::String(format!("{:.*}", decimals, number)))
+    }
+
+    fn handle_clamp(&mut self, value: &Box<Node>, lo: &Box<Node>, hi: &Box<Node>) -> InterpreterResult<Values> {
+        let value = self.handle_int(value, "clamp")?;
+        let lo = self.handle_int(lo, "clamp")?;
+        let hi = self.handle_int(hi, "clamp")?;
+
+        if lo > hi {
+            return Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot clamp between {} and {}, lower bound is greater than upper bound", lo, hi),
+                position: None
+            });
+        }
+
+        Ok(Values::Integer(value.clamp(lo, hi)))
+    }
+
+    fn handle_abs_diff(&mut self, left: &Box<Node>, right: &Box<Node>) -> InterpreterResult<Values> {
+        let left = self.handle_int(left, "abs_diff")?;
+        let right = self.handle_int(right, "abs_diff")?;
+
+        match i64::try_from(left.abs_diff(right)) {
+            Ok(diff) => Ok(Values::Integer(diff)),
+            Err(_) => Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Difference between {} and {} overflows", left, right),
+                position: None
+            })
+        }
+    }
+
+    // Stays an int while it fits, past 20! it becomes a big int
+    fn handle_factorial(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        let n = match self.handle_int(value, "factorial")? {
+            n if n >= 0 => n as u64,
+            n => return Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot @factorial a negative number {}", n),
+                position: None
+            })
+        };
+
+        let mut result = BigInt::from_u64(1);
+
+        for factor in 2..=n {
+            result.mul_small(factor);
+        }
+
+        match result.to_i64() {
+            Some(integer)   => Ok(Values::Integer(integer)),
+            None            => Ok(Values::BigInt(result))
+        }
+    }
+
+    fn handle_is_prime(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        let n = self.handle_int(value, "is_prime")?;
+
+        if n < 2 {
+            return Ok(Values::Boolean(false));
+        }
+
+        let mut divisor: i64 = 2;
+
+        // Comparing divisor against n / divisor avoids overflowing divisor * divisor
+        while divisor <= n / divisor {
+            if n % divisor == 0 {
+                return Ok(Values::Boolean(false));
+            }
+
+            divisor += 1;
+        }
+
+        Ok(Values::Boolean(true))
+    }
+
+    // Halfway values round away from zero, so 45 goes to 50 and -45 to -50
+    fn handle_round_to(&mut self, value: &Box<Node>, multiple: &Box<Node>) -> InterpreterResult<Values> {
+        let value = self.handle_int(value, "round_to")?;
+
+        let multiple = match self.handle_int(multiple, "round_to")? {
+            0 => return Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot @round_to a multiple of 0"),
+                position: None
+            }),
+            multiple => (multiple as i128).abs()
+        };
+
+        let (quotient, remainder) = (value as i128 / multiple, value as i128 % multiple);
+
+        let quotient = match remainder.abs() * 2 >= multiple {
+            true    => quotient + remainder.signum(),
+            false   => quotient
+        };
+
+        match i64::try_from(quotient * multiple) {
+            Ok(rounded) => Ok(Values::Integer(rounded)),
+            Err(_) => Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Rounding {} to a multiple of {} overflows", value, multiple),
+                position: None
+            })
+        }
+    }
+
+    fn handle_gcd(&mut self, left: &Box<Node>, right: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let left = self.handle_int(left, builtin)?;
+        let right = self.handle_int(right, builtin)?;
+
+        let (mut a, mut b) = (left.unsigned_abs(), right.unsigned_abs());
+
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+
+        let result = match builtin {
+            "lcm" if a == 0 => Some(0),
+            "lcm"           => (left.unsigned_abs() / a).checked_mul(right.unsigned_abs()),
+            _               => Some(a)
+        };
+
+        match result.and_then(|result| i64::try_from(result).ok()) {
+            Some(result) => Ok(Values::Integer(result)),
+            None => Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("@{} of {} and {} overflows", builtin, left, right),
+                position: None
+            })
+        }
+    }
+
+    fn handle_starts_with(&mut self, value: &Box<Node>, affix: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let value = self.handle_str(value, builtin)?;
+        let affix = self.handle_str(affix, builtin)?;
+
+        match builtin {
+            "ends_with" => Ok(Values::Boolean(value.ends_with(affix.as_str()))),
+            _           => Ok(Values::Boolean(value.starts_with(affix.as_str())))
+        }
+    }
+
+    // Picks the element with the smallest or largest key,
+    // the first one wins when keys are equal
+    fn handle_min_by(&mut self, function: &Box<Node>, array: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let function = self.handle_value(function.deref())?;
+
+        let values = match self.handle_value(array.deref())? {
+            Values::Array(values) => values,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected an array on @{}, but found {:?}", builtin, value.name()),
+                position: None
+            })
+        };
+
+        let mut picked: Option<(i64, Values)> = None;
+
+        for value in Rc::unwrap_or_clone(values) {
+            let key = match self.call_function(builtin, function.clone(), vec![value.clone()])? {
+                Values::Integer(key) => key,
+                key => return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Expected the @{} function to return an int, but found {:?}", builtin, key.name()),
+                    position: None
+                })
+            };
+
+            let better = match &picked {
+                Some((best, _)) if builtin == "max_by" => key > *best,
+                Some((best, _))                         => key < *best,
+                None                                    => true
+            };
+
+            if better {
+                picked = Some((key, value));
+            }
+        }
+
+        match picked {
+            Some((_, value)) => Ok(value),
+            None => Err(InterpreterError {
+                r#type: ErrorTypes::IndexOutOfBounds,
+                message: format!("Cannot @{} an empty array", builtin),
+                position: None
+            })
+        }
+    }
+
+    // -1 when nothing matches at or after start
+    fn handle_find_from(&mut self, array: &Box<Node>, value: &Box<Node>, start: &Box<Node>) -> InterpreterResult<Values> {
+        let values = match self.handle_value(array.deref())? {
+            Values::Array(values) => values,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected an array on @find_from, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        let value = self.handle_value(value.deref())?;
+
+        let start = match self.handle_int(start, "find_from")? {
+            start if start >= 0 => start as usize,
+            start => return Err(InterpreterError {
+                r#type: ErrorTypes::IndexOutOfBounds,
+                message: format!("Cannot @find_from a negative start of {}", start),
+                position: None
+            })
+        };
+
+        let index = values.iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, element)| **element == value)
+            .map(|(index, _)| index as i64);
+
+        Ok(Values::Integer(index.unwrap_or(-1)))
+    }
+
+    fn handle_count(&mut self, haystack: &Box<Node>, needle: &Box<Node>) -> InterpreterResult<Values> {
+        let haystack = self.handle_value(haystack.deref())?;
+        let needle = self.handle_value(needle.deref())?;
+
+        match (haystack, needle) {
+            (Values::String(_), Values::String(needle)) if needle.is_empty() => Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot count an empty string"),
+                position: None
+            }),
+            (Values::String(haystack), Values::String(needle)) => {
+                Ok(Values::Integer(haystack.matches(needle.as_str()).count() as i64))
+            },
+            (Values::Array(values), needle) if values.iter().all(|value| discriminant(value) == discriminant(&needle)) => {
+                Ok(Values::Integer(values.iter().filter(|value| **value == needle).count() as i64))
+            },
+            (haystack, needle) => Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot count {:?} in {:?}", needle.name(), haystack.name()),
+                position: None
+            })
+        }
+    }
+
+    fn handle_replace(&mut self, source: &Box<Node>, from: &Box<Node>, to: &Box<Node>) -> InterpreterResult<Values> {
+        let source = self.handle_str(source, "replace")?;
+        let from = self.handle_str(from, "replace")?;
+        let to = self.handle_str(to, "replace")?;
+
+        if from.is_empty() {
+            return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot replace an empty string"),
+                position: None
+            });
+        }
+
+        Ok(Values::String(source.replace(from.as_str(), to.as_str())))
+    }
+
+    fn handle_trim(&mut self, value: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let value = self.handle_str(value, builtin)?;
+
+        let trimmed = match builtin {
+            "trim_start"    => value.trim_start(),
+            "trim_end"      => value.trim_end(),
+            _               => value.trim()
+        };
+
+        Ok(Values::String(trimmed.to_string()))
+    }
+
+    // The sign stays in front of the zeros and counts toward width, like -07
+    fn handle_zfill(&mut self, number: &Box<Node>, width: &Box<Node>) -> InterpreterResult<Values> {
+        let number = self.handle_int(number, "zfill")?;
+
+        let width = match self.handle_int(width, "zfill")? {
+            width if width >= 0 => width as usize,
+            width => return Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot @zfill to a negative width of {}", width),
+                position: None
+            })
+        };
+
+        Ok(Values::String(format!("{:0width$}", number, width = width)))
+    }
+
+    fn handle_pad(&mut self, value: &Box<Node>, width: &Box<Node>, fill: &Option<Box<Node>>, builtin: &str) -> InterpreterResult<Values> {
+        let value = match self.handle_value(value.deref())? {
+            Values::String(str)         => str,
+            Values::Integer(integer)    => integer.to_string(),
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot @{} {:?}, expected a string or int", builtin, value.name()),
+                position: None
+            })
+        };
+
+        let width = match self.handle_int(width, builtin)? {
+            width if width >= 0 => width as usize,
+            width => return Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot @{} to a negative width of {}", builtin, width),
+                position: None
+            })
+        };
+
+        let fill = match fill {
+            Some(fill) => {
+                let fill = self.handle_str(fill, builtin)?;
+                let mut chars = fill.chars();
+
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) => char,
+                    _ => return Err(InterpreterError {
+                        r#type: ErrorTypes::TypeError,
+                        message: format!("Expected a single fill char on @{}, but found {:?}", builtin, fill),
+                        position: None
+                    })
+                }
+            },
+            None => ' '
+        };
+
+        // Strings already wider than width are left as they are
+        let padding: String = std::iter::repeat_n(fill, width.saturating_sub(value.chars().count())).collect();
+
+        match builtin {
+            "pad_right" => Ok(Values::String(value + &padding)),
+            _           => Ok(Values::String(padding + &value))
+        }
+    }
+
+    fn handle_flatten(&mut self, array: &Box<Node>, depth: &Option<Box<Node>>) -> InterpreterResult<Values> {
+        let values = match self.handle_value(array.deref())? {
+            Values::Array(values) => values,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot flatten {:?}, expected an array", value.name()),
+                position: None
+            })
+        };
+
+        let depth = match depth {
+            Some(depth) => match self.handle_int(depth, "flatten")? {
+                depth if depth >= 0 => Some(depth),
+                depth => return Err(InterpreterError {
+                    r#type: ErrorTypes::MathError,
+                    message: format!("Cannot flatten with a negative depth of {}", depth),
+                    position: None
+                })
+            },
+            None => None
+        };
+
+        fn flatten(values: Vec<Values>, depth: Option<i64>, output: &mut Vec<Values>) {
+            for value in values {
+                match value {
+                    Values::Array(inner) if depth != Some(0) => flatten(Rc::unwrap_or_clone(inner), depth.map(|depth| depth - 1), output),
+                    value => output.push(value)
+                }
+            }
+        }
+
+        let mut flattened: Vec<Values> = vec![];
+        flatten(Rc::unwrap_or_clone(values), depth, &mut flattened);
+
+        Ok(Values::Array(Rc::new(flattened)))
+    }
+
+    fn handle_first_last(&mut self, value: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let element = match self.handle_value(value.deref())? {
+            Values::Array(values) => match builtin {
+                "last"  => values.last().cloned(),
+                _       => values.first().cloned()
+            },
+            Values::String(str) => match builtin {
+                "last"  => str.chars().last(),
+                _       => str.chars().next()
+            }.map(|char| Values::String(char.to_string())),
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot get @{} of {:?}, expected an array or string", builtin, value.name()),
+                position: None
+            })
+        };
+
+        match element {
+            Some(element) => Ok(element),
+            None => Err(InterpreterError {
+                r#type: ErrorTypes::IndexOutOfBounds,
+                message: format!("Cannot get @{} of an empty value", builtin),
+                position: None
+            })
+        }
+    }
+
+    fn handle_take_drop(&mut self, array: &Box<Node>, count: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let values = match self.handle_value(array.deref())? {
+            Values::Array(values) => values,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot @{} from {:?}, expected an array", builtin, value.name()),
+                position: None
+            })
+        };
+
+        let count = match self.handle_int(count, builtin)? {
+            count if count >= 0 => (count as usize).min(values.len()),
+            count => return Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot @{} a negative count of {}", builtin, count),
+                position: None
+            })
+        };
+
+        match builtin {
+            "drop"  => Ok(Values::Array(Rc::new(values[count..].to_vec()))),
+            _       => Ok(Values::Array(Rc::new(values[..count].to_vec())))
+        }
+    }
+
+    // There are no float values yet, so @avg rounds
+    // toward zero the same way / does in @math
+    fn handle_reduce(&mut self, array: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let values = match self.handle_value(array.deref())? {
+            Values::Array(values) => values,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot get @{} of {:?}, expected an array", builtin, value.name()),
+                position: None
+            })
+        };
+
+        let mut numbers: Vec<i64> = vec![];
+
+        for value in Rc::unwrap_or_clone(values) {
+            match value {
+                Values::Integer(integer) => numbers.push(integer),
+                value => return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot get @{} of an array holding {:?}, expected ints", builtin, value.name()),
+                    position: None
+                })
+            }
+        }
+
+        let overflow = || InterpreterError {
+            r#type: ErrorTypes::MathError,
+            message: format!("@{} overflows", builtin),
+            position: None
+        };
+
+        match builtin {
+            "product" => numbers.iter()
+                .try_fold(1i64, |product, number| product.checked_mul(*number))
+                .map(Values::Integer)
+                .ok_or_else(overflow),
+            "avg" if numbers.is_empty() => Err(InterpreterError {
+                r#type: ErrorTypes::MathError,
+                message: format!("Cannot get @avg of an empty array"),
+                position: None
+            }),
+            "avg" => {
+                let sum = numbers.iter()
+                    .try_fold(0i64, |sum, number| sum.checked_add(*number))
+                    .ok_or_else(overflow)?;
+
+                // There are no floats yet, so an average that isn't whole can't be returned
+                if sum % numbers.len() as i64 != 0 {
+                    return Err(InterpreterError {
+                        r#type: ErrorTypes::MathError,
+                        message: format!("@avg is not a whole number ({} / {})", sum, numbers.len()),
+                        position: None
+                    });
+                }
+
+                Ok(Values::Integer(sum / numbers.len() as i64))
+            },
+            _ => numbers.iter()
+                .try_fold(0i64, |sum, number| sum.checked_add(*number))
+                .map(Values::Integer)
+                .ok_or_else(overflow)
+        }
+    }
+
+    // Cloning an array only shares its Rc, so this rebuilds every array,
+    // including ones nested in arrays, records and sourced namespaces.
+    // Functions keep sharing their body, it's never changed after parsing.
+    fn handle_deepcopy(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        fn deepcopy(value: &Values) -> Values {
+            match value {
+                Values::Array(values)   => Values::Array(Rc::new(values.iter().map(deepcopy).collect())),
+                Values::Record(fields)  => Values::Record(fields.iter().map(|(name, value)| (*name, deepcopy(value))).collect()),
+                Values::Namespace(vars) => Values::Namespace(vars.iter().map(|(name, value)| (*name, deepcopy(value))).collect()),
+                value                   => value.clone()
+            }
+        }
+
+        let value = self.handle_value(value.deref())?;
+
+        Ok(deepcopy(&value))
+    }
+
+    fn handle_enumerate(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        let values = match self.handle_value(value.deref())? {
+            Values::Array(values)   => Rc::unwrap_or_clone(values),
+            Values::String(str)     => str.chars().map(|char| Values::String(char.to_string())).collect(),
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot @enumerate {:?}, expected an array or string", value.name()),
+                position: None
+            })
+        };
+
+        let pairs = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| Values::Array(Rc::new(vec![Values::Integer(index as i64), value])))
+            .collect();
+
+        Ok(Values::Array(Rc::new(pairs)))
+    }
+
+    fn handle_chars(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        let str = self.handle_str(value, "chars")?;
+
+        Ok(Values::Array(Rc::new(str.chars().map(|char| Values::String(char.to_string())).collect())))
+    }
+
+    // Splits on \n or \r\n, a trailing newline doesn't make an empty last line
+    fn handle_lines(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        let str = self.handle_str(value, "lines")?;
+
+        Ok(Values::Array(Rc::new(str.lines().map(|line| Values::String(line.to_string())).collect())))
+    }
+
+    // Uppercases the first char of the string, or of every
+    // whitespace separated word for title_case, and lowercases the rest
+    fn handle_capitalize(&mut self, value: &Box<Node>, builtin: &str) -> InterpreterResult<Values> {
+        let str = self.handle_str(value, builtin)?;
+
+        let mut capitalized = String::new();
+        let mut word_start = true;
+
+        for char in str.chars() {
+            if word_start && !char.is_whitespace() {
+                capitalized.extend(char.to_uppercase());
+                word_start = false;
+            } else {
+                capitalized.extend(char.to_lowercase());
+            }
+
+            if builtin == "title_case" && char.is_whitespace() {
+                word_start = true;
+            }
+        }
+
+        Ok(Values::String(capitalized))
+    }
+
+    // Marks the function so calls to it with hashable args are cached,
+    // only worth it for functions that don't depend on anything else.
+    // A redeclared function is a new function and isn't memoized.
+    fn handle_memoize(&mut self, function: &Box<Node>) -> InterpreterResult<Values> {
+        let scope = match self.handle_value(function.deref())? {
+            Values::Function { scope, .. } => scope,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a function on @memoize, but found {:?}", value.name()),
+                position: None
+            })
+        };
+
+        self.memos.entry(Rc::as_ptr(&scope)).or_insert_with(|| Memo {
+            _scope: scope.clone(),
+            results: HashMap::new()
+        });
+
+        Ok(Values::None)
+    }
+
+    fn handle_empty(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        match self.handle_value(value.deref())? {
+            Values::String(str)     => Ok(Values::Boolean(str.is_empty())),
+            Values::Array(values)   => Ok(Values::Boolean(values.is_empty())),
+            Values::Record(fields)  => Ok(Values::Boolean(fields.is_empty())),
+            value @ (Values::Integer(_) |
+                     Values::BigInt(_)  |
+                     Values::Boolean(_)) => Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot check if {:?} is @empty", value.name()),
+                position: None
+            }),
+            _ => Ok(Values::Boolean(false))
+        }
+    }
+
+    // Evaluates value count times, one by default, and prints how long
+    // it took on average to stderr, handing back the last result
+    fn handle_bench(&mut self, value: &Box<Node>, count: &Option<Box<Node>>) -> InterpreterResult<Values> {
+        let count = match count {
+            Some(count) => match self.handle_int(count, "bench")? {
+                count if count > 0 && count <= u32::MAX as i64 => count as u32,
+                count => return Err(InterpreterError {
+                    r#type: ErrorTypes::MathError,
+                    message: format!("Cannot @bench {} times, expected at least once", count),
+                    position: None
+                })
+            },
+            None => 1
+        };
+
+        let mut result = Values::None;
+        let start = Instant::now();
+
+        for _ in 0..count {
+            result = self.handle_value(value.deref())?;
+        }
+
+        let elapsed = start.elapsed();
+
+        match count {
+            1 => eprintln!("@bench took {:?}", elapsed),
+            _ => eprintln!("@bench took {:?} on average over {} runs", elapsed / count, count)
+        }
+
+        Ok(result)
+    }
+
+    // Unlike log this shows the raw value with its type, like
+    // Array([Integer(1), Integer(2)]), and hands the value back
+    fn handle_debug(&mut self, value: &Box<Node>) -> InterpreterResult<Values> {
+        let value = self.handle_value(value.deref())?;
+        eprintln!("{:?}", value);
+
+        Ok(value)
+    }
+
+    fn handle_clear(&mut self) -> InterpreterResult<Values> {
+        self.write_output("\x1b[2J\x1b[H")?;
+
+        Ok(Values::None)
+    }
+
+    fn handle_move_cursor(&mut self, row: &Box<Node>, col: &Box<Node>) -> InterpreterResult<Values> {
+        let row = match self.handle_value(row.deref())? {
+            Values::Integer(row) if row > 0 => row,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot move cursor to row {:?}, expected a positive int", value.name()),
+                position: None
+            })
+        };
+
+        let col = match self.handle_value(col.deref())? {
+            Values::Integer(col) if col > 0 => col,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot move cursor to col {:?}, expected a positive int", value.name()),
+                position: None
+            })
+        };
+
+        self.write_output(&format!("\x1b[{};{}H", row, col))?;
+
+        Ok(Values::None)
+    }
+
+    fn handle_var(&mut self, identifier: &Box<Node>, value: &Box<Node>) -> InterpreterResult<Values> {
+        let name = match identifier.deref() {
+            Node::Identifier(identifier) => identifier,
+            _ => unreachable!(),
+        };
 
         let val = self.handle_value(value.deref())?;
-        self.env.borrow_mut().set(name.as_str(), val);
+        self.env.borrow_mut().set(*name, val);
+
+        Ok(Values::None)
+    }
+
+    fn handle_destructure(&mut self, targets: &[Node], value: &Box<Node>) -> InterpreterResult<Values> {
+        let values = match self.handle_value(value.deref())? {
+            Values::Array(values) if values.len() == targets.len() => Rc::unwrap_or_clone(values),
+            Values::Array(values) => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot set {} names from an array of {}", targets.len(), values.len()),
+                position: None
+            }),
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Cannot set [...] from {:?}, expected an array", value.name()),
+                position: None
+            })
+        };
+
+        for (target, value) in targets.iter().zip(values) {
+            if let Node::Identifier(name) = target {
+                self.env.borrow_mut().set(*name, value);
+            }
+        }
 
         Ok(Values::None)
     }
@@ -371,27 +2240,30 @@ impl Interpreter {
     fn handle_array_access(&mut self, identifier: &Box<Node>, index: &Box<Node>) -> InterpreterResult<Values> {
         match identifier.deref() {
             Node::Identifier(name) => {
-                let array = match self.env.borrow().get(&name)? {
+                let array = match self.env.borrow().get(*name)? {
                     Values::Array(array) => array,
                     _ => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Cannot access {:?}. {:?} is not a array", name, name)
+                        message: format!("Cannot access {:?}. {:?} is not a array", name, name),
+                        position: None
                     })
                 };
 
                 let index = match self.handle_value(index)? {
-                    Values::Integer(index) => index as usize,
+                    Values::Integer(index) => index,
                     value => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Cannot access {:?} with {:?}", name, value.name())
+                        message: format!("Cannot access {:?} with {:?}", name, value.name()),
+                        position: None
                     })
                 };
 
-                match array.get(index.clone()) {
+                match resolve_index(index, array.len()).and_then(|resolved| array.get(resolved)) {
                     Some(value) => Ok(value.clone()),
                     None => return Err(InterpreterError {
                         r#type: ErrorTypes::IndexOutOfBounds,
-                        message: format!("Cannot access {}[{}]", name, index)
+                        message: format!("Cannot access {}[{}]", name, index),
+                        position: None
                     })
                 }
             },
@@ -399,44 +2271,49 @@ impl Interpreter {
                 let inner_value = self.handle_array_access(inner_identifier, inner_index)?;
 
                 let index = match self.handle_value(index)? {
-                    Values::Integer(index) => index as usize,
+                    Values::Integer(index) => index,
                     value => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Cannot access [{:?}] with {:?}", inner_index, value.name())
+                        message: format!("Cannot access [{:?}] with {:?}", inner_index, value.name()),
+                        position: None
                     })
                 };
 
                 let inner_index = match self.handle_value(inner_index)? {
-                    Values::Integer(inner_index) => inner_index as usize,
+                    Values::Integer(inner_index) => inner_index,
                     value => return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Cannot access [{:?}] with {:?}", inner_index, value.name())
+                        message: format!("Cannot access [{:?}] with {:?}", inner_index, value.name()),
+                        position: None
                     })
                 };
 
                 if let Values::Array(array) = inner_value {
-                    match array.get(index.clone()) {
+                    match resolve_index(index, array.len()).and_then(|resolved| array.get(resolved)) {
                         Some(value) => Ok(value.clone()),
                         None => return Err(InterpreterError {
                             r#type: ErrorTypes::IndexOutOfBounds,
-                            message: format!("Cannot access [{}][{}]", inner_index, index)
+                            message: format!("Cannot access [{}][{}]", inner_index, index),
+                            position: None
                         })
                     }
                 } else {
                     Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
-                        message: format!("Expected an array for nested access but found a non-array value")
+                        message: format!("Expected an array for nested access but found a non-array value"),
+                        position: None
                     })
                 }
             },
             _ => Err(InterpreterError {
                 r#type: ErrorTypes::TypeError,
                 message: format!("Array access expression is invalid"),
+                position: None,
             })
         }
     }
 
-    fn handle_update(&mut self, identifier: &Box<Node>, value: &Box<Node>) -> InterpreterResult<Values> {
+    fn handle_update(&mut self, identifier: &Box<Node>, value: &Box<Node>, global: bool) -> InterpreterResult<Values> {
         let name = match identifier.deref() {
             Node::Identifier(identifier) => identifier,
             _ => unreachable!(),
@@ -444,9 +2321,16 @@ impl Interpreter {
 
         let val = self.handle_value(value.deref())?;
 
-        match self.env.borrow().get(name.as_str()) {
+        let variable = match global {
+            true    => self.env.borrow().get_global(*name),
+            false   => self.env.borrow().get(*name)
+        };
+
+        match variable {
             Ok(variable) => {
-                if discriminant(&val) != discriminant(&variable) {
+                // A variable set to none is a placeholder, the first update fills in its type.
+                // Clearing a typed variable back to none isn't allowed, or it could change type.
+                if discriminant(&val) != discriminant(&variable) && !variable.is_none() {
                     return Err(InterpreterError {
                         r#type: ErrorTypes::TypeError,
                         message: format!(
@@ -454,17 +2338,48 @@ impl Interpreter {
                             val.name(),
                             variable.name()
                         ),
+                        position: None,
                     });
                 }
             }
             Err(err) => return Err(err),
         }
 
-        self.env.borrow_mut().update(name.as_str(), val)?;
+        match global {
+            true    => self.env.borrow_mut().update_global(*name, val)?,
+            false   => self.env.borrow_mut().update(*name, val)?
+        };
+
+        Ok(Values::None)
+    }
+
+    // Each value goes back to wherever the other variable was defined
+    fn handle_swap(&mut self, left: &Box<Node>, right: &Box<Node>) -> InterpreterResult<Values> {
+        let (left, right) = match (left.deref(), right.deref()) {
+            (Node::Identifier(left), Node::Identifier(right)) => (left, right),
+            _ => unreachable!(),
+        };
+
+        let left_value = self.env.borrow().get(*left)?;
+        let right_value = self.env.borrow().get(*right)?;
+
+        self.env.borrow_mut().update(*left, right_value)?;
+        self.env.borrow_mut().update(*right, left_value)?;
 
         Ok(Values::None)
     }
 
+    // inc/dec are an update with the variable plus/minus step
+    fn handle_inc(&mut self, identifier: &Box<Node>, op: &str, step: &Box<Node>) -> InterpreterResult<Values> {
+        let value = Node::MathExpr {
+            left: identifier.clone(),
+            op: op.to_string(),
+            right: step.clone(),
+        };
+
+        self.handle_update(identifier, &Box::new(value), false)
+    }
+
     fn handle_log(&mut self, log_type: &str, args: &Vec<Box<Node>>) -> InterpreterResult<Values> {
         let mut output = String::new();
 
@@ -472,28 +2387,24 @@ impl Interpreter {
             let value = self.handle_value(arg.deref())?;
 
             match value {
-                Values::Integer(integer)    => output.push_str(integer.to_string().as_str()),
-                Values::String(str)         => output.push_str(str.as_str()),
-                Values::Boolean(boolean)    => output.push_str(boolean.to_string().as_str()),
-                Values::Array(values)       => output.push_str(
-                    (values.iter()
-                        .map(|value| value.name())
-                        .collect::<Vec<String>>())
-                        .join(" ")
-                        .as_str()
-                ),
-                _ => {
+                Values::Function { .. } |
+                Values::Namespace(_)    |
+                Values::Break               => {
                     return Err(InterpreterError {
                         r#type: ErrorTypes::UnknownError,
                         message: format!("Something went wrong while handling log args"),
+                        position: None,
                     })
-                }
+                },
+                value                       => output.push_str(value.display().as_str())
             }
         }
 
         match log_type {
-            "log"   => print!("{output}"),
-            "logl"  => println!("{output}"),
+            "log"   => self.write_output(&output)?,
+            "logl"  => self.write_output(&format!("{output}\n"))?,
+            "elog"  => eprint!("{output}"),
+            "elogl" => eprintln!("{output}"),
             _       => (),
         }
 
@@ -501,59 +2412,115 @@ impl Interpreter {
     }
 
     fn handle_check(&mut self, condition: &Box<Node>, scope: &Box<Node>) -> InterpreterResult<Values> {
-        let new_env = Rc::new(RefCell::new(
-            Env::new(
-                Some(self.env.clone()),
-                self.env.borrow().cwd.clone()
-            )
-        ));
-
-        let prev_env = std::mem::replace(&mut self.env, new_env);
-
-        if let Values::Boolean(condition) = self.handle_condition(condition)? {
-            if condition {
-                if let Node::Scope { body } = scope.deref() {
-                    for scope_node in body {
-                        if let Node::Break = scope_node.deref() {
-                            return Ok(Values::Break)
-                        }
-
-                        let ret_value = self.exec_node(scope_node.deref())?;
-                        if !ret_value.is_none() {
-                            return Ok(ret_value);
-                        }
-                    }
-                }
+        if let Values::Boolean(true) = self.handle_condition(condition)? {
+            if let Node::Scope { body } = scope.deref() {
+                return self.handle_branch(body);
+            }
+        }
+
+        Ok(Values::None)
+    }
+
+    // Running out of steps or time can't be caught,
+    // otherwise a script could get around its limits
+    fn handle_try(&mut self, body: &Box<Node>, catch_var: &Box<Node>, catch_scope: &Box<Node>) -> InterpreterResult<Values> {
+        let err = match body.deref() {
+            Node::Scope { body } => match self.handle_branch(body) {
+                Ok(value) => return Ok(value),
+                Err(err) if matches!(err.r#type, ErrorTypes::FuelExhausted | ErrorTypes::TimeoutError) => return Err(err),
+                Err(err) => err
+            },
+            _ => return Ok(Values::None)
+        };
+
+        self.backtrace.clear();
+
+        let name = match catch_var.deref() {
+            Node::Identifier(name) => name,
+            _ => unreachable!()
+        };
+
+        let catch_env = Rc::new(RefCell::new(Env::new(Some(self.env.clone()))));
+
+        catch_env.borrow_mut().set(*name, Values::String(err.message));
+
+        let mut guard = EnvGuard::new(self, catch_env);
+
+        match catch_scope.deref() {
+            Node::Scope { body } => guard.handle_branch(body),
+            _ => Ok(Values::None)
+        }
+    }
+
+    // Only the picked value is evaluated, so a function call
+    // on the other side never runs
+    fn handle_if_expr(&mut self, cond: &Box<Node>, then_val: &Box<Node>, else_val: &Box<Node>) -> InterpreterResult<Values> {
+        match self.handle_condition(cond)? {
+            Values::Boolean(true) => self.handle_value(then_val.deref()),
+            _ => self.handle_value(else_val.deref())
+        }
+    }
+
+    fn handle_match(&mut self, value: &Box<Node>, arms: &Vec<(Node, Node)>, default: &Option<Box<Node>>) -> InterpreterResult<Values> {
+        let value = self.handle_value(value.deref())?;
+        let mut matched = default.as_deref();
+
+        for (pattern, scope) in arms {
+            if self.handle_value(pattern)? == value {
+                matched = Some(scope);
+                break;
             }
         }
 
-        self.env = prev_env;
+        let body = match matched {
+            Some(Node::Scope { body }) => body,
+            _ => return Ok(Values::None)
+        };
+
+        self.handle_branch(body)
+    }
+
+    // Runs the body of a taken branch in its own env, passing
+    // up a ret value or break like check does
+    fn handle_branch(&mut self, body: &Vec<Box<Node>>) -> InterpreterResult<Values> {
+        let new_env = Rc::new(RefCell::new(Env::new(Some(self.env.clone()))));
+
+        let mut guard = EnvGuard::new(self, new_env);
+
+        for scope_node in body {
+            if scope_node.is_break() {
+                return Ok(Values::Break);
+            }
+
+            let ret_value = guard.exec_node(scope_node.deref())?;
+            if !ret_value.is_none() {
+                return Ok(ret_value);
+            }
+        }
 
         Ok(Values::None)
     }
 
-    fn handle_while(&mut self, condition: &Box<Node>, scope: &Box<Node>) -> InterpreterResult<Values> {
-        let new_env = Rc::new(RefCell::new(
-            Env::new(
-                Some(self.env.clone()),
-                self.env.borrow().cwd.clone()
-            )
-        ));
+    fn handle_while(&mut self, condition: &Box<Node>, scope: &Box<Node>, else_scope: &Option<Box<Node>>) -> InterpreterResult<Values> {
+        let new_env = Rc::new(RefCell::new(Env::new(Some(self.env.clone()))));
 
-        let prev_env = std::mem::replace(&mut self.env, new_env);
+        let mut guard = EnvGuard::new(self, new_env);
 
-        while let Values::Boolean(condition) = self.handle_condition(condition)? {
+        while let Values::Boolean(condition) = guard.handle_condition(condition)? {
             if !condition {
                 break;
             }
 
+            guard.consume_fuel()?;
+            guard.check_timeout()?;
+
             if let Node::Scope { body } = scope.deref() {
                 for scope_node in body {
-                    if let Node::Break = scope_node.deref() {
+                    if scope_node.is_break() {
                         return Ok(Values::None);
                     }
 
-                    let ret_value = self.exec_node(scope_node.deref())?;
+                    let ret_value = guard.exec_node(scope_node.deref())?;
                     if ret_value.is_break() {
                         return Ok(Values::None);
                     }
@@ -565,9 +2532,37 @@ impl Interpreter {
             }
         }
 
-        self.env = prev_env;
+        drop(guard);
+
+        // Only reached when the loop ended without a break
+        if let Some(else_scope) = else_scope {
+            if let Node::Scope { body } = else_scope.deref() {
+                return self.handle_branch(body);
+            }
+        }
+
+        Ok(Values::None)
+    }
+
+    fn handle_require(&mut self, condition: &Box<Node>, message: &Box<Node>) -> InterpreterResult<Values> {
+        if let Values::Boolean(true) = self.handle_condition(condition)? {
+            return Ok(Values::None);
+        }
+
+        let message = match self.handle_value(message.deref())? {
+            Values::String(str) => str,
+            value => return Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a string message on require, but found {:?}", value.name()),
+                position: None,
+            })
+        };
 
-        Ok(Values::None)
+        Err(InterpreterError {
+            r#type: ErrorTypes::ValidationError,
+            message,
+            position: None,
+        })
     }
 
     fn handle_array(&mut self, values: &Vec<Literals>) -> InterpreterResult<Values> {
@@ -575,16 +2570,52 @@ impl Interpreter {
 
         for value in values {
             let value = match value {
-                Literals::Int(integer)      => Values::Integer(integer.clone()),
-                Literals::String(str)       => Values::String(str.clone()),
-                Literals::Boolean(boolean)  => Values::Boolean(boolean.clone()),
-                Literals::Array(values)     => self.handle_array(values)?
+                Literals::Int(integer)          => Values::Integer(integer.clone()),
+                Literals::String(str)           => Values::String(str.clone()),
+                Literals::Boolean(boolean)      => Values::Boolean(boolean.clone()),
+                Literals::None                  => Values::None,
+                Literals::Array(values)         => self.handle_array(values)?,
+                Literals::Identifier(name)      => self.env.borrow().get(*name)?
             };
 
             parsed_values.push(value);
         }
 
-        Ok(Values::Array(parsed_values))
+        // The parser can only check the types of literals,
+        // names are checked here once their values are known
+        if let Some(first) = parsed_values.first() {
+            if let Some(value) = parsed_values.iter().find(|value| discriminant(*value) != discriminant(first)) {
+                return Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot have two or more types in array, found {} and {}", first.name(), value.name()),
+                    position: None
+                });
+            }
+        }
+
+        Ok(Values::Array(Rc::new(parsed_values)))
+    }
+
+    fn handle_int(&mut self, node: &Node, builtin: &str) -> InterpreterResult<i64> {
+        match self.handle_value(node)? {
+            Values::Integer(integer) => Ok(integer),
+            value => Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected an int on @{}, but found {:?}", builtin, value.name()),
+                position: None
+            })
+        }
+    }
+
+    fn handle_str(&mut self, node: &Node, builtin: &str) -> InterpreterResult<String> {
+        match self.handle_value(node)? {
+            Values::String(str) => Ok(str),
+            value => Err(InterpreterError {
+                r#type: ErrorTypes::TypeError,
+                message: format!("Expected a string on @{}, but found {:?}", builtin, value.name()),
+                position: None
+            })
+        }
     }
 
     fn handle_value(&mut self, node: &Node) -> InterpreterResult<Values> {
@@ -592,15 +2623,77 @@ impl Interpreter {
             Node::Literal(Literals::Int(integer))       => Ok(Values::Integer(integer.clone())),
             Node::Literal(Literals::String(str))        => Ok(Values::String(str.clone())),
             Node::Literal(Literals::Boolean(boolean))   => Ok(Values::Boolean(boolean.clone())),
+            Node::Literal(Literals::None)               => Ok(Values::None),
             Node::Literal(Literals::Array(values))      => self.handle_array(values),
             Node::ArrayAccess { identifier, index }     => self.handle_array_access(identifier, index),
-            Node::Identifier(identifier)                => self.env.borrow().get(identifier.as_str()),
+            Node::Identifier(identifier)                => self.env.borrow().get(*identifier),
             Node::FunctionCall { identifier, args }     => self.handle_fn_call(identifier, args),
             Node::MathExpr { left, op, right }          => self.handle_math(left, op, right),
             Node::Random { start, end }                 => self.handle_random(start, end),
+            Node::Args                                  => self.handle_args(),
+            Node::Arg(index)                            => self.handle_arg(index),
+            Node::Exit(code)                            => self.handle_exit(code),
+            Node::Raise(message)                        => self.handle_raise(message),
+            Node::ReadKey                               => self.handle_read_key(),
+            Node::InputInt { prompt }                   => self.handle_input_int(prompt),
+            Node::Color { value, color }                => self.handle_color(value, color),
+            Node::Format { template, args }             => self.handle_format(template, args),
+            Node::Clear                                 => self.handle_clear(),
+            Node::MoveCursor { row, col }               => self.handle_move_cursor(row, col),
+            Node::ParseCsv(source)                      => self.handle_parse_csv(source),
+            Node::ToCsv(rows)                           => self.handle_to_csv(rows),
+            Node::AbsPath(path)                         => self.handle_abs_path(path),
+            Node::Exists(path)                          => self.handle_path_check(path, "exists"),
+            Node::IsFile(path)                          => self.handle_path_check(path, "is_file"),
+            Node::IsDir(path)                           => self.handle_path_check(path, "is_dir"),
+            Node::Fixed { value, decimals }             => self.handle_fixed(value, decimals),
+            Node::Clamp { value, lo, hi }               => self.handle_clamp(value, lo, hi),
+            Node::Count { haystack, needle }            => self.handle_count(haystack, needle),
+            Node::Replace { source, from, to }          => self.handle_replace(source, from, to),
+            Node::Flatten { array, depth }              => self.handle_flatten(array, depth),
+            Node::ZFill { number, width }               => self.handle_zfill(number, width),
+            Node::PadLeft { value, width, fill }        => self.handle_pad(value, width, fill, "pad_left"),
+            Node::PadRight { value, width, fill }       => self.handle_pad(value, width, fill, "pad_right"),
+            Node::First(value)                          => self.handle_first_last(value, "first"),
+            Node::Last(value)                           => self.handle_first_last(value, "last"),
+            Node::Take { array, count }                 => self.handle_take_drop(array, count, "take"),
+            Node::Drop { array, count }                 => self.handle_take_drop(array, count, "drop"),
+            Node::DeepCopy(value)                       => self.handle_deepcopy(value),
+            // A plain copy, arrays inside it still share their storage until one is changed
+            Node::Clone(value)                          => self.handle_value(value.deref()),
+            Node::Enumerate(value)                      => self.handle_enumerate(value),
+            Node::Chars(value)                          => self.handle_chars(value),
+            Node::Empty(value)                          => self.handle_empty(value),
+            Node::Memoize(function)                     => self.handle_memoize(function),
+            Node::Bench { value, count }                => self.handle_bench(value, count),
+            Node::Capitalize(value)                     => self.handle_capitalize(value, "capitalize"),
+            Node::TitleCase(value)                      => self.handle_capitalize(value, "title_case"),
+            Node::Record(fields)                        => self.handle_record(fields),
+            Node::Choice(array)                         => self.handle_choice(array),
+            Node::Lines(value)                          => self.handle_lines(value),
+            Node::Factorial(value)                      => self.handle_factorial(value),
+            Node::IsPrime(value)                        => self.handle_is_prime(value),
+            Node::RoundTo { value, multiple }           => self.handle_round_to(value, multiple),
+            Node::Gcd { left, right }                   => self.handle_gcd(left, right, "gcd"),
+            Node::FindFrom { array, value, start }      => self.handle_find_from(array, value, start),
+            Node::MinBy { function, array }             => self.handle_min_by(function, array, "min_by"),
+            Node::MaxBy { function, array }             => self.handle_min_by(function, array, "max_by"),
+            Node::StartsWith { value, prefix }          => self.handle_starts_with(value, prefix, "starts_with"),
+            Node::EndsWith { value, suffix }            => self.handle_starts_with(value, suffix, "ends_with"),
+            Node::Lcm { left, right }                   => self.handle_gcd(left, right, "lcm"),
+            Node::Debug(value)                          => self.handle_debug(value),
+            Node::IfExpr { cond, then_val, else_val }   => self.handle_if_expr(cond, then_val, else_val),
+            Node::Sum(array)                            => self.handle_reduce(array, "sum"),
+            Node::Product(array)                        => self.handle_reduce(array, "product"),
+            Node::Avg(array)                            => self.handle_reduce(array, "avg"),
+            Node::Trim(value)                           => self.handle_trim(value, "trim"),
+            Node::TrimStart(value)                      => self.handle_trim(value, "trim_start"),
+            Node::TrimEnd(value)                        => self.handle_trim(value, "trim_end"),
+            Node::AbsDiff { left, right }               => self.handle_abs_diff(left, right),
             _ => Err(InterpreterError {
                 r#type: ErrorTypes::UnknownError,
                 message: format!("Something went wrong while handling value"),
+                position: None,
             }),
         }
     }
@@ -612,51 +2705,198 @@ impl Interpreter {
                 let right_value = self.handle_value(right.deref())?;
 
                 match (left_value, right_value) {
+                    // Anything can be checked against none, as in x == none
+                    (left_value, right_value) if left_value.is_none() || right_value.is_none() => match condition.as_str() {
+                        "==" => Ok(Values::Boolean(left_value.is_none() && right_value.is_none())),
+                        "!=" => Ok(Values::Boolean(!(left_value.is_none() && right_value.is_none()))),
+                        _ => Err(InterpreterError {
+                            r#type: ErrorTypes::TypeError,
+                            message: format!("Cannot order none with {}, only == and != work on none", condition),
+                            position: None,
+                        })
+                    },
                     (Values::Integer(left_int), Values::Integer(right_int))         => Ok(Values::Boolean(compare!(left_int, condition, right_int))),
                     (Values::String(left_str), Values::String(right_str))           => Ok(Values::Boolean(compare!(left_str, condition, right_str))),
-                    (Values::Boolean(left_boolean), Values::Boolean(right_boolean)) => Ok(Values::Boolean(compare!(left_boolean, condition, right_boolean))),
+                    // false < true holds in rust but means nothing here, so booleans only test equality
+                    (Values::Boolean(left_boolean), Values::Boolean(right_boolean)) => match condition.as_str() {
+                        "==" => Ok(Values::Boolean(left_boolean == right_boolean)),
+                        "!=" => Ok(Values::Boolean(left_boolean != right_boolean)),
+                        _ => Err(InterpreterError {
+                            r#type: ErrorTypes::TypeError,
+                            message: format!("Cannot order booleans with {}, only == and != work on booleans", condition),
+                            position: None,
+                        })
+                    },
+                    // Arrays are equal when every element is, nested arrays included
+                    (Values::Array(left_array), Values::Array(right_array)) => match condition.as_str() {
+                        "==" => Ok(Values::Boolean(left_array == right_array)),
+                        "!=" => Ok(Values::Boolean(left_array != right_array)),
+                        _ => Err(InterpreterError {
+                            r#type: ErrorTypes::TypeError,
+                            message: format!("Cannot order arrays with {}, only == and != work on arrays", condition),
+                            position: None,
+                        })
+                    },
                     _ => {
                         return Err(InterpreterError {
                             r#type: ErrorTypes::TypeError,
                             message: format!("Cannot compare {:?} to {:?}", left, right),
+                            position: None,
                         })
                     }
                 }
             }
-            Node::Literal(literal) => match literal {
-                Literals::Int(integer)      => Ok(Values::Boolean(*integer > 0)),
-                Literals::String(str)       => Ok(Values::Boolean(str.len() > 0)),
-                Literals::Boolean(boolean)  => Ok(Values::Boolean(*boolean)),
-                Literals::Array(values)     => Ok(Values::Boolean(values.len() > 0)),
+            Node::LogicalExpr { left, op, right } => {
+                let left = self.handle_condition(left)?;
+
+                // Short circuit, the right side only runs when it can change the result
+                match (op.as_str(), left) {
+                    ("&&", Values::Boolean(false))  => Ok(Values::Boolean(false)),
+                    ("||", Values::Boolean(true))   => Ok(Values::Boolean(true)),
+                    _                               => self.handle_condition(right),
+                }
             },
-            _ => {
-                return Err(InterpreterError {
-                    r#type: ErrorTypes::UnknownError,
-                    message: format!("Something went wrong in handle_while"),
+            Node::Not(condition) => match self.handle_condition(condition)? {
+                Values::Boolean(boolean) => Ok(Values::Boolean(!boolean)),
+                value => Err(InterpreterError {
+                    r#type: ErrorTypes::TypeError,
+                    message: format!("Cannot negate {:?}", value.name()),
+                    position: None,
                 })
+            },
+            // Anything else is a value like x, arr[0] or @has_next list
+            _ => {
+                let value = self.handle_value(condition.deref())?;
+
+                match value.truthy() {
+                    Some(truthy) => Ok(Values::Boolean(truthy)),
+                    None => Err(InterpreterError {
+                        r#type: ErrorTypes::TypeError,
+                        message: format!("Cannot use {:?} as a condition", value.name()),
+                        position: None,
+                    })
+                }
             }
         }
     }
 
     fn exec_node(&mut self, node: &Node) -> InterpreterResult<Values> {
+        if let Node::Statement { line, col, node } = node {
+            return self.exec_node(node).map_err(|err| InterpreterError {
+                position: err.position.or(Some((*line, *col))),
+                ..err
+            });
+        }
+
+        self.consume_fuel()?;
+        self.check_timeout()?;
+
         match node {
             Node::Function { identifier, args, scope }  => self.handle_fn(identifier, args, scope),
             Node::FunctionCall { identifier, args }     => self.handle_fn_call(identifier, args),
             Node::Return(value)                         => self.handle_ret(value),
-            Node::Source { file_name, cwd, ast }        => self.handle_source(file_name, cwd, ast),
+            Node::Source { file_name, alias }           => self.handle_source(file_name, alias),
             Node::Scope { body }                        => self.handle_scope(body),
             Node::MathExpr { left, op, right }          => self.handle_math(left, op, right),
             Node::Random { start, end }                 => self.handle_random(start, end),
+            Node::Exit(code)                            => self.handle_exit(code),
+            Node::Raise(message)                        => self.handle_raise(message),
+            Node::Memoize(function)                     => self.handle_memoize(function),
+            Node::ColorLog { value, color }             => self.handle_color_log(value, color),
+            Node::Debug(value)                          => self.handle_debug(value).map(|_| Values::None),
+            Node::Bench { value, count }                => self.handle_bench(value, count).map(|_| Values::None),
+            Node::Clear                                 => self.handle_clear(),
+            Node::MoveCursor { row, col }               => self.handle_move_cursor(row, col),
             Node::Var { identifier, value }             => self.handle_var(identifier, value),
-            Node::Update { identifier, value }          => self.handle_update(identifier, value),
+            Node::Destructure { targets, value }        => self.handle_destructure(targets, value),
+            Node::Swap { left, right }                  => self.handle_swap(left, right),
+            Node::Update {
+                identifier,
+                value,
+                global
+            }                                           => self.handle_update(identifier, value, *global),
+            Node::Inc { identifier, step }              => self.handle_inc(identifier, "+", step),
+            Node::Dec { identifier, step }              => self.handle_inc(identifier, "-", step),
             Node::Check { condition, scope }            => self.handle_check(condition, scope),
-            Node::While { condition, scope }            => self.handle_while(condition, scope),
+            Node::Try {
+                body,
+                catch_var,
+                catch_scope
+            }                                           => self.handle_try(body, catch_var, catch_scope),
+            Node::While {
+                condition,
+                scope,
+                else_scope
+            }                                           => self.handle_while(condition, scope, else_scope),
+            Node::Require { condition, message }        => self.handle_require(condition, message),
+            Node::Match { value, arms, default }        => self.handle_match(value, arms, default),
             Node::Log { r#type, args }                  => self.handle_log(r#type.as_str(), args),
             _                                           => Ok(Values::None),
         }
     }
 
+    // Writes the top level variables as name = value lines, sorted by name.
+    // Functions and namespaces can't be written, they show up as comments.
+    pub fn dump_state(&self) -> String {
+        let env = self.env.borrow();
+
+        let mut names: Vec<&Symbol> = env.vars.keys().collect();
+        names.sort_by_key(|name| name.name());
+
+        let mut state = String::new();
+
+        for name in names {
+            match dump_value(&env.vars[name]) {
+                Some(value) => state.push_str(&format!("{} = {}\n", name, value)),
+                None if matches!(env.vars[name], Values::Function { .. }) => state.push_str(&format!("# {} = <function>\n", name)),
+                None        => state.push_str(&format!("# {} = <{}>\n", name, env.vars[name].name()))
+            }
+        }
+
+        state
+    }
+
+    // Sets the variables written by dump_state on the top level env
+    pub fn load_state(&mut self, state: &str) -> InterpreterResult<()> {
+        for (i, line) in state.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |message: String| InterpreterError {
+                r#type: ErrorTypes::ParseError,
+                message: format!("Cannot load state on line {}: {}", i + 1, message),
+                position: None
+            };
+
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value),
+                None => return Err(parse_error("expected name = value".to_string()))
+            };
+
+            let mut chars = value.chars().peekable();
+            let value = load_value(&mut chars).map_err(parse_error)?;
+
+            if chars.any(|char| !char.is_whitespace()) {
+                return Err(parse_error(format!("unexpected text after the value of {:?}", name)));
+            }
+
+            self.env.borrow_mut().set(Symbol::intern(name), value);
+        }
+
+        Ok(())
+    }
+
+    // Functions that were running when the last error happened, innermost first
+    pub fn backtrace(&self) -> &[String] {
+        &self.backtrace
+    }
+
     pub fn run(&mut self, ast: &Vec<Node>) -> InterpreterResult<()> {
+        self.backtrace.clear();
+
         for node in ast {
             self.exec_node(node)?;
         }
@@ -664,3 +2904,607 @@ impl Interpreter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_source_with, AsplError};
+
+    // A writer the test keeps a handle on, to read back what was logged
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Runs src on an interpreter prepared by setup, giving back
+    // what it logged along with how it ended
+    fn run_in(cwd: PathBuf, src: &str, setup: impl FnOnce(&mut Interpreter)) -> (String, Result<(), AsplError>) {
+        let output = SharedOutput::default();
+
+        let mut interpreter = Interpreter::with_output(cwd, Box::new(output.clone()));
+        interpreter.input = Some(Box::new(io::Cursor::new(String::new())));
+        setup(&mut interpreter);
+
+        let result = run_source_with(&mut interpreter, src);
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+
+        (output, result)
+    }
+
+    fn run_with(src: &str, setup: impl FnOnce(&mut Interpreter)) -> (String, Result<(), AsplError>) {
+        run_in(env::current_dir().unwrap(), src, setup)
+    }
+
+    fn run(src: &str) -> (String, Result<(), AsplError>) {
+        run_with(src, |_| ())
+    }
+
+    fn run_with_input(src: &str, input: &str) -> (String, Result<(), AsplError>) {
+        let input = input.to_string();
+        run_with(src, |interpreter| interpreter.input = Some(Box::new(io::Cursor::new(input))))
+    }
+
+    // String literals can't hold a ", so text like quoted csv is set from here
+    fn run_with_str(src: &str, name: &str, value: &str) -> (String, Result<(), AsplError>) {
+        run_with(src, |interpreter| interpreter.env.borrow_mut().set(Symbol::intern(name), Values::String(value.to_string())))
+    }
+
+    // A fresh dir under the system temp dir, for tests that need files
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("aspl-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn interpreter_error(result: Result<(), AsplError>) -> InterpreterError {
+        match result {
+            Err(AsplError::Interpreter(err)) => err,
+            result => panic!("Expected an interpreter error, but got {:?}", result)
+        }
+    }
+
+    #[test]
+    fn exit_with_a_non_int_code_is_a_type_error() {
+        let (_, result) = run("@exit \"1\"");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::TypeError));
+    }
+
+    #[test]
+    fn read_key_reads_a_line_from_canned_input() {
+        let (output, result) = run_with_input("logl @read_key", "a\nb\n");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "a\n");
+    }
+
+    #[cfg(feature = "raw-input")]
+    #[test]
+    fn read_key_skips_raw_mode_for_canned_input() {
+        // Raw mode is only for a terminal on stdin, given input is read by line
+        let (output, result) = run_with_input("logl @read_key \"!\"", "yes\n");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "yes!\n");
+    }
+
+    #[cfg(not(feature = "raw-input"))]
+    #[test]
+    fn read_raw_key_without_the_feature_is_an_error() {
+        let mut interpreter = Interpreter::new(env::current_dir().unwrap());
+        let err = interpreter.read_raw_key().unwrap_err();
+
+        assert!(matches!(err.r#type, ErrorTypes::IoError));
+    }
+
+    // Both cases in one test, since NO_COLOR is shared by the whole process
+    #[test]
+    fn color_wraps_in_escape_codes_unless_no_color_is_set() {
+        env::remove_var("NO_COLOR");
+
+        let (output, result) = run("logl @color \"hi\" \"red\"");
+        assert!(result.is_ok());
+        assert_eq!(output, format!("{color_red}hi{color_reset}\n"));
+
+        env::set_var("NO_COLOR", "1");
+        let (output, result) = run("@colorl \"hi\" \"green\"");
+        env::remove_var("NO_COLOR");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "hi\n");
+    }
+
+    #[test]
+    fn color_with_an_unknown_name_is_an_error() {
+        let (_, result) = run("logl @color \"hi\" \"pink\"");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::TypeError));
+    }
+
+    #[test]
+    fn clear_and_move_cursor_write_escape_sequences() {
+        let (output, result) = run("@clear\n@move_cursor 3 7");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "\x1b[2J\x1b[H\x1b[3;7H");
+    }
+
+    #[test]
+    fn move_cursor_to_row_zero_is_an_error() {
+        let (_, result) = run("@move_cursor 0 1");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::TypeError));
+    }
+
+    #[test]
+    fn parse_csv_splits_simple_rows() {
+        let (output, result) = run("logl @parse_csv \"a,b\nc,d\"");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "[[a, b], [c, d]]\n");
+    }
+
+    #[test]
+    fn parse_csv_keeps_commas_in_quoted_fields() {
+        let (output, result) = run_with_str("set rows @parse_csv csv\nlogl rows[0][0] \"|\" rows[0][1]", "csv", "\"x,y\",z");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "x,y|z\n");
+    }
+
+    #[test]
+    fn parse_csv_keeps_newlines_in_quoted_fields() {
+        let (output, result) = run_with_str("set rows @parse_csv csv\nlogl rows[0][0] \"|\" rows[1][0]", "csv", "\"one\ntwo\",b\nc,d");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "one\ntwo|c\n");
+    }
+
+    #[test]
+    fn to_csv_quotes_fields_that_need_it() {
+        let (output, result) = run_with_str("logl @to_csv [[\"a,b\", quote], [\"line\nbreak\", \"plain\"]]", "quote", "say \"hi\"");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "\"a,b\",\"say \"\"hi\"\"\"\n\"line\nbreak\",plain\n");
+    }
+
+    #[test]
+    fn to_csv_round_trips_through_parse_csv() {
+        let src = "set rows [[\"a,b\", quote], [\"line\nbreak\", \"plain\"]]\n\
+                   set back @parse_csv @to_csv rows\n\
+                   check back == rows { logl \"same\" }";
+
+        let (output, result) = run_with_str(src, "quote", "say \"hi\"");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "same\n");
+    }
+
+    #[test]
+    fn to_csv_rejects_rows_that_are_not_arrays() {
+        let (_, result) = run("logl @to_csv [1, 2]");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::TypeError));
+    }
+
+    #[test]
+    fn require_passes_silently_when_true() {
+        let (output, result) = run("set age 20\nrequire age >= 18 \"too young\"\nlogl \"ok\"");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "ok\n");
+    }
+
+    #[test]
+    fn require_fails_with_its_message_as_a_validation_error() {
+        let (output, result) = run("set age 12\nrequire age >= 18 \"too young\"\nlogl \"ok\"");
+        let err = interpreter_error(result);
+
+        assert_eq!(output, "");
+        assert!(matches!(err.r#type, ErrorTypes::ValidationError));
+        assert_eq!(err.message, "too young");
+    }
+
+    #[test]
+    fn source_resolves_from_the_interpreters_cwd() {
+        let dir = temp_dir("source-cwd");
+        fs::write(dir.join("lib.aspl"), "set greeting \"hi\"").unwrap();
+
+        let (output, result) = run_in(dir, "@source \"lib.aspl\"\nlogl greeting", |_| ());
+
+        assert!(result.is_ok());
+        assert_eq!(output, "hi\n");
+    }
+
+    #[test]
+    fn source_on_the_line_after_a_log_is_not_its_arg() {
+        let dir = temp_dir("source-after-log");
+        fs::write(dir.join("lib.aspl"), "set greeting \"hi\"").unwrap();
+
+        let (output, result) = run_in(dir, "logl \"x\"\n@source \"lib.aspl\"\nlogl greeting", |_| ());
+
+        assert!(result.is_ok());
+        assert_eq!(output, "x\nhi\n");
+    }
+
+    #[test]
+    fn errors_inside_scopes_leave_the_outer_env_intact() {
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(env::current_dir().unwrap(), Box::new(output.clone()));
+
+        let scopes = [
+            "check true { set inner 1\n@error \"boom\" }",
+            "while true { set inner 1\n@error \"boom\" }",
+            "fn fail { set inner 1\n@error \"boom\" }\n@fail",
+            "{ set inner 1\n@error \"boom\" }",
+        ];
+
+        run_source_with(&mut interpreter, "set outer 1").unwrap();
+
+        for scope in scopes {
+            assert!(run_source_with(&mut interpreter, scope).is_err());
+
+            // Still at the top level, so the scope's var is gone
+            let result = run_source_with(&mut interpreter, "logl outer\nlogl inner");
+            assert!(matches!(interpreter_error(result).r#type, ErrorTypes::UndefinedVar));
+        }
+
+        assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "1\n".repeat(scopes.len()));
+    }
+
+    #[test]
+    fn nested_arrays_compare_by_value() {
+        let src = "set a [[1, 2], [3]]\n\
+                   set b [[1, 2], [3]]\n\
+                   set c [[1, 2], [4]]\n\
+                   check a == b { logl \"a == b\" }\n\
+                   check a != c { logl \"a != c\" }\n\
+                   check a == c { logl \"a == c\" }";
+
+        let (output, result) = run(src);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "a == b\na != c\n");
+    }
+
+    #[test]
+    fn ordering_arrays_is_a_type_error() {
+        let (_, result) = run("set a [1]\ncheck a < a { logl \"less\" }");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::TypeError));
+    }
+
+    #[test]
+    fn state_round_trips_through_dump_and_load() {
+        let src = "set lines \"a\nb\"\n\
+                   set crlf \"c\r\nd\"\n\
+                   set nested [[\"w\"], [\"x\ny\"]]\n\
+                   set flag true\n\
+                   set count 42\n\
+                   fn skipped { ret 1 }";
+
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(env::current_dir().unwrap(), Box::new(output.clone()));
+        interpreter.env.borrow_mut().set(Symbol::intern("quoted"), Values::String("say \"hi\" \\ bye".to_string()));
+        run_source_with(&mut interpreter, src).unwrap();
+
+        let state = interpreter.dump_state();
+        assert!(state.contains("lines = \"a\\nb\"\n"), "{}", state);
+        assert!(state.contains("# skipped = <function>\n"), "{}", state);
+
+        let mut loaded = Interpreter::new(env::current_dir().unwrap());
+        loaded.load_state(&state).unwrap();
+        // Functions are only noted, so they don't come back
+        assert_eq!(loaded.dump_state(), state.replace("# skipped = <function>\n", ""));
+
+        for name in ["lines", "crlf", "nested", "flag", "count", "quoted"] {
+            let name = Symbol::intern(name);
+            assert_eq!(loaded.env.borrow().get(name).unwrap(), interpreter.env.borrow().get(name).unwrap());
+        }
+    }
+
+    #[test]
+    fn bare_identifiers_check_their_truthiness() {
+        let src = "set negative @math(0 - 3)\n\
+                   set zero 0\n\
+                   set empty \"\"\n\
+                   set items [1]\n\
+                   check negative { logl \"negative\" }\n\
+                   check zero { logl \"zero\" }\n\
+                   check empty { logl \"empty\" }\n\
+                   check items { logl \"items\" }";
+
+        let (output, result) = run(src);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "negative\nitems\n");
+    }
+
+    #[test]
+    fn log_formats_array_strings_like_scalar_strings() {
+        let (output, result) = run("logl \"a\"\nlogl [\"a\", \"b\"]\nlogl [[1, 2], [3]]\nlog \"x\" [\"y\"]");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "a\n[a, b]\n[[1, 2], [3]]\nx[y]");
+    }
+
+    #[test]
+    fn logging_a_function_is_an_error() {
+        let (output, result) = run("fn f { ret 1 }\nlogl f");
+
+        interpreter_error(result);
+        assert_eq!(output, "");
+    }
+
+    // Logs its name when called, so a test can tell which branches ran
+    const SIDE_EFFECT: &str = "fn side name {\nlogl \"ran \" name\nret 1\n}\nset n 0\n";
+
+    #[test]
+    fn if_only_evaluates_the_taken_branch() {
+        let (output, result) = run(&format!("{SIDE_EFFECT}logl @if n (@side \"then\") (@side \"else\")"));
+        assert!(result.is_ok());
+        assert_eq!(output, "ran else\n1\n");
+
+        let (output, result) = run(&format!("{SIDE_EFFECT}logl @if true (@side \"then\") (@side \"else\")"));
+        assert!(result.is_ok());
+        assert_eq!(output, "ran then\n1\n");
+    }
+
+    #[test]
+    fn and_skips_the_right_side_when_the_left_is_false() {
+        let (output, result) = run(&format!("{SIDE_EFFECT}check (n == 1) && (1 == @side \"right\") {{ logl \"taken\" }}"));
+        assert!(result.is_ok());
+        assert_eq!(output, "");
+
+        let (output, result) = run(&format!("{SIDE_EFFECT}check (n == 0) && (1 == @side \"right\") {{ logl \"taken\" }}"));
+        assert!(result.is_ok());
+        assert_eq!(output, "ran right\ntaken\n");
+    }
+
+    #[test]
+    fn or_skips_the_right_side_when_the_left_is_true() {
+        let (output, result) = run(&format!("{SIDE_EFFECT}check (n == 0) || (1 == @side \"right\") {{ logl \"taken\" }}"));
+        assert!(result.is_ok());
+        assert_eq!(output, "taken\n");
+
+        let (output, result) = run(&format!("{SIDE_EFFECT}check (n == 1) || (1 == @side \"right\") {{ logl \"taken\" }}"));
+        assert!(result.is_ok());
+        assert_eq!(output, "ran right\ntaken\n");
+    }
+
+    #[test]
+    fn grouped_conditions_short_circuit_inside_the_group() {
+        let src = format!("{SIDE_EFFECT}check ((n == 1) && (1 == @side \"inner\")) || (n == 0) {{ logl \"taken\" }}");
+        let (output, result) = run(&src);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "taken\n");
+    }
+
+    #[test]
+    fn comments_end_at_the_line_but_not_inside_strings() {
+        let (output, result) = run("logl \"count #1\"\nset x 5# note\nlogl x # trailing");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "count #1\n5\n");
+    }
+
+    #[test]
+    fn script_starting_with_a_shebang_runs() {
+        let (output, result) = run("#!/usr/bin/env aspl\nlogl \"hi\"");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "hi\n");
+    }
+
+    #[test]
+    fn deepcopy_shares_no_array_storage_with_the_original() {
+        // Whether any array in copy is the same allocation as the one in original
+        fn shares_storage(original: &Values, copy: &Values) -> bool {
+            match (original, copy) {
+                (Values::Array(original), Values::Array(copy)) => {
+                    Rc::ptr_eq(original, copy) ||
+                        original.iter().zip(copy.iter()).any(|(original, copy)| shares_storage(original, copy))
+                },
+                (Values::Record(original), Values::Record(copy)) |
+                (Values::Namespace(original), Values::Namespace(copy)) => {
+                    original.iter().any(|(name, original)| shares_storage(original, &copy[name]))
+                },
+                _ => false
+            }
+        }
+
+        let dir = temp_dir("deepcopy");
+        fs::write(dir.join("lib.aspl"), "set items [[1, 2], [3]]").unwrap();
+
+        let mut interpreter = Interpreter::new(dir);
+        let src = "set a [[1, 2], [3]]\n\
+                   set b @deepcopy a\n\
+                   set c a\n\
+                   set d @clone a\n\
+                   set r @record \"items\" a\n\
+                   set s @deepcopy r\n\
+                   @source \"lib.aspl\" as lib\n\
+                   set l @deepcopy lib";
+
+        run_source_with(&mut interpreter, src).unwrap();
+
+        let get = |name: &str| interpreter.env.borrow().get(Symbol::intern(name)).unwrap();
+
+        assert_eq!(get("a"), get("b"));
+        assert!(!shares_storage(&get("a"), &get("b")));
+        assert!(shares_storage(&get("a"), &get("c")));
+
+        assert_eq!(get("a"), get("d"));
+        assert!(shares_storage(&get("a"), &get("d")));
+
+        assert_eq!(get("r"), get("s"));
+        assert!(!shares_storage(&get("r"), &get("s")));
+
+        assert_eq!(get("lib"), get("l"));
+        assert!(!shares_storage(&get("lib"), &get("l")));
+    }
+
+    // Logs every time the body actually runs, so the output counts the calls
+    const COUNTING_FIB: &str = "fn fib n {\n\
+                                logl \"call\"\n\
+                                check n < 2 { ret n }\n\
+                                set a @fib @math(n - 1)\n\
+                                set b @fib @math(n - 2)\n\
+                                ret @math(a + b)\n\
+                                }\n";
+
+    #[test]
+    fn memoized_fib_runs_once_per_n() {
+        let (output, result) = run(&format!("{COUNTING_FIB}@memoize fib\nset result @fib 30\nlog result"));
+
+        assert!(result.is_ok());
+        assert!(output.ends_with("832040"), "{}", output);
+        // fib 0 through fib 30, instead of the 2.6 million calls without it
+        assert_eq!(output.matches("call").count(), 31);
+    }
+
+    #[test]
+    fn plain_fib_calls_grow_exponentially() {
+        let (output, result) = run(&format!("{COUNTING_FIB}set result @fib 15\nlog result"));
+
+        assert!(result.is_ok());
+        assert!(output.ends_with("610"), "{}", output);
+        assert_eq!(output.matches("call").count(), 1973);
+    }
+
+    #[test]
+    fn memoized_functions_with_the_same_name_keep_separate_caches() {
+        let src = "fn double {\n\
+                   fn f x { ret @math(x * 2) }\n\
+                   ret f\n\
+                   }\n\
+                   fn triple {\n\
+                   fn f x { ret @math(x * 3) }\n\
+                   ret f\n\
+                   }\n\
+                   set d @double\n\
+                   set t @triple\n\
+                   @memoize d\n\
+                   @memoize t\n\
+                   logl (@d 5) \" \" (@t 5)";
+
+        let (output, result) = run(src);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "10 15\n");
+    }
+
+    #[test]
+    fn redeclaring_a_memoized_function_starts_uncached() {
+        let src = "fn sq x { ret @math(x * x) }\n\
+                   @memoize sq\n\
+                   logl (@sq 4)\n\
+                   fn sq x { ret 0 }\n\
+                   logl (@sq 4)";
+
+        let (output, result) = run(src);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "16\n0\n");
+    }
+
+    #[test]
+    fn bare_log_before_a_call_or_group_on_the_next_line() {
+        let src = "fn hi {\nlogl \"hi\"\nret 1\n}\n\
+                   logl\n@hi\n\
+                   logl\n(@hi)";
+
+        let (output, result) = run(src);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(output, "\nhi\n\nhi\n");
+    }
+
+    #[test]
+    fn bare_log_with_a_dangling_token_on_its_line_is_an_error() {
+        let (_, result) = run("logl )");
+        assert!(matches!(result, Err(AsplError::Parser(_))));
+    }
+
+    #[test]
+    fn update_global_skips_a_shadowing_binding() {
+        let (output, result) = run("set counter 0\n{ set counter 10\nupdate global counter 1\nlogl counter }\nlogl counter");
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(output, "10\n1\n");
+    }
+
+    #[test]
+    fn global_is_a_keyword_and_not_a_variable_name() {
+        for src in ["set global 5", "update global 5", "set x 1\nupdate global global 5"] {
+            let (_, result) = run(src);
+            assert!(matches!(result, Err(AsplError::Parser(_))), "{}: {:?}", src, result);
+        }
+    }
+
+    #[test]
+    fn runtime_errors_carry_the_position_of_the_failing_statement() {
+        let (_, result) = run("set x 1\nfn half n {\n  @error \"bad\"\n}\n@half 2");
+        assert_eq!(result.unwrap_err().position(), Some((3, 3)));
+
+        let (_, result) = run("set x 1\n\nset y @math(x / 0)");
+        assert_eq!(result.unwrap_err().position(), Some((3, 1)));
+    }
+
+    #[test]
+    fn errors_in_a_sourced_file_point_at_the_source_call() {
+        let dir = temp_dir("source-position");
+        fs::write(dir.join("lib.aspl"), "set x 1\n@error \"bad\"").unwrap();
+
+        let (_, result) = run_in(dir, "set y 2\n@source \"lib.aspl\"", |_| ());
+
+        assert_eq!(result.unwrap_err().position(), Some((2, 1)));
+    }
+
+    #[test]
+    fn break_still_ends_a_loop_from_inside_a_check() {
+        let (output, result) = run("set i 0\nwhile true {\n  check i == 2 {\n    break\n  }\n  inc i\n}\nlogl i");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "2\n");
+    }
+
+    #[test]
+    fn avg_of_a_whole_number_average_is_an_int() {
+        let (output, result) = run("logl @avg [2, 4, 9]");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "5\n");
+    }
+
+    #[test]
+    fn avg_that_is_not_a_whole_number_is_a_math_error() {
+        let (_, result) = run("logl @avg [1, 2]");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::MathError));
+
+        let (_, result) = run("logl @avg []");
+        assert!(matches!(interpreter_error(result).r#type, ErrorTypes::MathError));
+    }
+
+    #[test]
+    fn update_fills_in_a_none_placeholder() {
+        let (output, result) = run("set x none\nupdate x 5\nlogl x");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "5\n");
+    }
+
+    #[test]
+    fn update_cannot_change_type_by_going_through_none() {
+        let (_, result) = run("set x 1\nupdate x none\nupdate x \"str\"");
+        let err = interpreter_error(result);
+
+        assert!(matches!(err.r#type, ErrorTypes::TypeError));
+        assert_eq!(err.position, Some((2, 1)));
+    }
+}
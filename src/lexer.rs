@@ -5,7 +5,9 @@ pub enum TokenTypes {
     StringLiteral,
     IntLiteral,
     BooleanLiteral,
+    NoneLiteral,
     FnCall,
+    Eq,
     EqEq,
     NotEq,
     GThan,
@@ -14,6 +16,7 @@ pub enum TokenTypes {
     LThanEq,
     AND,
     OR,
+    Not,
     Add,
     Sub,
     Mul,
@@ -23,7 +26,9 @@ pub enum TokenTypes {
     OpenCurly,
     CloseCurly,
     OpenBracket,
-    CloseBracket
+    CloseBracket,
+    Comma,
+    Ellipsis
 }
 
 impl TokenTypes {
@@ -35,13 +40,20 @@ impl TokenTypes {
     pub fn is_close_curly(&self)    -> bool { matches!(self, TokenTypes::CloseCurly) }
     pub fn is_open_bracket(&self)   -> bool { matches!(self, TokenTypes::OpenBracket) }
     pub fn is_close_bracket(&self)  -> bool { matches!(self, TokenTypes::CloseBracket) }
+    pub fn is_comma(&self)          -> bool { matches!(self, TokenTypes::Comma) }
+    pub fn is_eq(&self)             -> bool { matches!(self, TokenTypes::Eq) }
+    pub fn is_ellipsis(&self)       -> bool { matches!(self, TokenTypes::Ellipsis) }
     pub fn is_fn_call(&self)        -> bool { matches!(self, TokenTypes::FnCall) }
+    pub fn is_and(&self)            -> bool { matches!(self, TokenTypes::AND) }
+    pub fn is_or(&self)             -> bool { matches!(self, TokenTypes::OR) }
+    pub fn is_not(&self)            -> bool { matches!(self, TokenTypes::Not) }
 
     pub fn is_literal(&self) -> bool{
         return matches!(self,
             TokenTypes::IntLiteral |
             TokenTypes::StringLiteral |
-            TokenTypes::BooleanLiteral
+            TokenTypes::BooleanLiteral |
+            TokenTypes::NoneLiteral
         );
     }
 
@@ -85,7 +97,9 @@ pub struct Lexer<T: Iterator<Item = char> + Clone> {
 #[derive(Debug)]
 pub struct LexerError {
     pub message: String,
-    pub char: Option<char>
+    pub char: Option<char>,
+    pub line: usize,
+    pub col: usize
 }
 
 pub type LexerResult<T> = Result<T, LexerError>;
@@ -94,22 +108,34 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
     pub fn new(mut chars: T) -> Self {
         let current_char = chars.next();
 
-        Self {
+        let mut lexer = Self {
             chars,
             current_char,
             line: 1,
             col: 1
+        };
+
+        // A #!/usr/bin/env aspl line lets scripts run directly, skip it
+        // up to the newline, which still gets counted as line 1
+        if lexer.current_char == Some('#') && lexer.peek() == Some('!') {
+            while lexer.current_char.is_some_and(|char| char != '\n') {
+                lexer.advance();
+            }
         }
+
+        lexer
     }
 
     fn lex_str_lit(&mut self) -> LexerResult<Token> {
         let mut buffer = String::new();
+        let mut terminated = false;
 
         // Ignore "
         self.advance();
 
         while let Some(char) = &self.current_char {
             if char.eq(&'"') {
+                terminated = true;
                 self.advance();
                 break;
             }
@@ -118,6 +144,15 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
             self.advance();
         }
 
+        if !terminated {
+            return Err(LexerError {
+                message: format!("Unterminated string literal starting at {}:{}", self.line, self.col),
+                char: Some('"'),
+                line: self.line,
+                col: self.col
+            });
+        }
+
         let str_lit = Ok(Token {
             r#type: TokenTypes::StringLiteral,
             value: Some(buffer.to_owned()),
@@ -155,8 +190,8 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
     fn lex_identifier(&mut self) -> LexerResult<Token> {
         let mut buffer = String::new();
 
-        while let Some(char) = &self.current_char {
-            if !char.is_alphanumeric() && char != &'_' {
+        while let Some(char) = self.current_char {
+            if !char.is_alphanumeric() && char != '_' && !self.is_qualifier(char, &buffer) {
                 break;
             }
 
@@ -166,10 +201,15 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
 
         let identifier = match buffer.as_str() {
             "log"   | "logl"    |
+            "elog"  | "elogl"   |
             "set"   | "update"  |
             "check" | "while"   |
             "fn"    | "ret"     |
-            "break" => Token {
+            "break" | "require" |
+            "match" | "inc"     |
+            "dec"   | "try"     |
+            "catch" | "swap"    |
+            "global"            => Token {
                 r#type: TokenTypes::Statement,
                 value: Some(buffer.to_owned()),
                 line: self.line,
@@ -181,6 +221,12 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
                 line: self.line,
                 col: self.col
             },
+            "none" => Token {
+                r#type: TokenTypes::NoneLiteral,
+                value: Some(buffer.to_owned()),
+                line: self.line,
+                col: self.col
+            },
             _ => Token {
                 r#type: TokenTypes::Identifier,
                 value: Some(buffer.to_owned()),
@@ -198,8 +244,8 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
 
         let mut buffer = String::new();
 
-        while let Some(char) = &self.current_char {
-            if !char.is_alphanumeric() && char != &'_' {
+        while let Some(char) = self.current_char {
+            if !char.is_alphanumeric() && char != '_' && !self.is_qualifier(char, &buffer) {
                 break;
             }
 
@@ -225,11 +271,13 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
             '>' if self.peek().unwrap_or_default() == '=' => Some(TokenTypes::GThanEq),
             '<' if self.peek().unwrap_or_default() == '=' => Some(TokenTypes::LThanEq),
 
+            '=' => Some(TokenTypes::Eq),
             '>' => Some(TokenTypes::GThan),
             '<' => Some(TokenTypes::LThan),
 
             '&' if self.peek().unwrap_or_default() == '&' => Some(TokenTypes::AND),
             '|' if self.peek().unwrap_or_default() == '|' => Some(TokenTypes::OR),
+            '!' => Some(TokenTypes::Not),
 
             '+' => Some(TokenTypes::Add),
             '-' => Some(TokenTypes::Sub),
@@ -245,6 +293,11 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
             '[' => Some(TokenTypes::OpenBracket),
             ']' => Some(TokenTypes::CloseBracket),
 
+            ',' => Some(TokenTypes::Comma),
+
+            '.' if self.peek().unwrap_or_default() == '.' &&
+                   self.chars.clone().nth(1) == Some('.') => Some(TokenTypes::Ellipsis),
+
             _ => None,
         };
 
@@ -259,6 +312,11 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
                 self.advance();
             }
 
+            if token_type == TokenTypes::Ellipsis {
+                self.advance();
+                self.advance();
+            }
+
             self.advance();
 
             return Ok(Token {
@@ -271,15 +329,43 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
 
         Err(LexerError {
             message: "Unexpected end of input while lexing symbol".to_string(),
-            char: None
+            char: None,
+            line: self.line,
+            col: self.col
         })
     }
 
     pub fn lex(&mut self) -> LexerResult<Vec<Token>> {
-        let mut parsed_tokens: Vec<Token> = vec![];
+        self.collect()
+    }
+
+    // A `.` joining two names, like lib.greet
+    fn is_qualifier(&mut self, char: char, buffer: &str) -> bool {
+        char == '.' &&
+            !buffer.is_empty() &&
+            self.peek().is_some_and(|next| next.is_alphabetic() || next == '_')
+    }
+
+    fn advance(&mut self) {
+        self.current_char = self.chars.next();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.clone().next()
+    }
+}
+
+// Yields one token per next(), so the parser can pull tokens as it
+// goes instead of waiting for the whole file to be lexed
+impl<T: Iterator<Item = char> + Clone> Iterator for Lexer<T> {
+    type Item = LexerResult<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
         let mut comment = false;
 
         while let Some(char) = self.current_char {
+            // Strings are lexed whole, so only a # outside of one starts
+            // a comment, even right after a token like `set x 5# note`
             if char == '#' {
                 comment = true;
             }
@@ -307,45 +393,70 @@ impl<T: Iterator<Item = char> + Clone> Lexer<T> {
             }
 
             if char == '"' {
-                let str_lit = self.lex_str_lit()?;
-                parsed_tokens.push(str_lit);
-
-                continue;
+                return Some(self.lex_str_lit());
             }
 
             if char.is_numeric() {
-                let str_int = self.lex_int_lit()?;
-                parsed_tokens.push(str_int);
-
-                continue;
+                return Some(self.lex_int_lit());
             }
 
             if (char.is_alphanumeric() || char == '_') && !char.is_numeric() {
-                let identifier = self.lex_identifier()?;
-                parsed_tokens.push(identifier);
-
-                continue;
+                return Some(self.lex_identifier());
             }
 
             if char == '@' {
-                let fn_call = self.lex_fn_call()?;
-                parsed_tokens.push(fn_call);
-
-                continue;
+                return Some(self.lex_fn_call());
             }
 
-            let symbol = self.lex_symbol(char)?;
-            parsed_tokens.push(symbol);
+            return Some(self.lex_symbol(char));
         }
 
-        Ok(parsed_tokens)
+        None
     }
+}
 
-    fn advance(&mut self) {
-        self.current_char = self.chars.next();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(src: &str) -> Vec<(TokenTypes, Option<String>)> {
+        Lexer::new(src.chars())
+            .lex()
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.r#type, token.value))
+            .collect()
     }
 
-    fn peek(&mut self) -> Option<char> {
-        self.chars.clone().next()
+    #[test]
+    fn hash_inside_a_string_is_not_a_comment() {
+        assert_eq!(
+            lex("log \"count #1\""),
+            vec![
+                (TokenTypes::Statement, Some("log".to_string())),
+                (TokenTypes::StringLiteral, Some("count #1".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn hash_right_after_a_token_starts_a_comment() {
+        assert_eq!(
+            lex("set x 5# note"),
+            vec![
+                (TokenTypes::Statement, Some("set".to_string())),
+                (TokenTypes::Identifier, Some("x".to_string())),
+                (TokenTypes::IntLiteral, Some("5".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn leading_shebang_line_is_skipped() {
+        let tokens = Lexer::new("#!/usr/bin/env aspl\nlogl 1".chars()).lex().unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].r#type, TokenTypes::Statement);
+        assert_eq!(tokens[0].line, 2);
     }
 }
@@ -0,0 +1,119 @@
+pub mod lexer;
+pub mod parser;
+pub mod interpreter;
+pub mod symbol;
+
+use std::path::PathBuf;
+
+pub use interpreter::Interpreter;
+pub use lexer::Lexer;
+pub use parser::Parser;
+
+use interpreter::InterpreterError;
+use lexer::LexerError;
+use parser::{Node, ParserError};
+
+// An error from any of the three phases of running aspl source
+#[derive(Debug)]
+pub enum AsplError {
+    Lexer(LexerError),
+    Parser(ParserError),
+    Interpreter(InterpreterError),
+}
+
+impl AsplError {
+    pub fn phase(&self) -> &str {
+        match self {
+            AsplError::Lexer(_)         => "lexer",
+            AsplError::Parser(_)        => "parser",
+            AsplError::Interpreter(_)   => "interpreter",
+        }
+    }
+
+    // The interpreter's ErrorTypes name, or the phase's own error name
+    pub fn kind(&self) -> String {
+        match self {
+            AsplError::Lexer(_)         => "LexingError".to_string(),
+            AsplError::Parser(_)        => "ParsingError".to_string(),
+            AsplError::Interpreter(err) => format!("{:?}", err.r#type),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AsplError::Lexer(err)       => &err.message,
+            AsplError::Parser(err)      => &err.message,
+            AsplError::Interpreter(err) => &err.message,
+        }
+    }
+
+    // Line and col of the error, for the interpreter that's the statement it failed in
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            AsplError::Lexer(err)       => Some((err.line, err.col)),
+            AsplError::Parser(err)      => err.token.as_ref().map(|token| (token.line, token.col)),
+            AsplError::Interpreter(err) => err.position,
+        }
+    }
+}
+
+impl From<LexerError> for AsplError {
+    fn from(err: LexerError) -> Self {
+        AsplError::Lexer(err)
+    }
+}
+
+impl From<ParserError> for AsplError {
+    fn from(err: ParserError) -> Self {
+        AsplError::Parser(err)
+    }
+}
+
+impl From<InterpreterError> for AsplError {
+    fn from(err: InterpreterError) -> Self {
+        AsplError::Interpreter(err)
+    }
+}
+
+// Lexes, parses and runs src with a fresh interpreter.
+// Sourced files resolve relative to the current directory.
+pub fn run_source(src: &str, cwd: PathBuf) -> Result<(), AsplError> {
+    let mut interpreter = Interpreter::new(cwd);
+
+    run_source_with(&mut interpreter, src)
+}
+
+// Same as run_source, but on an interpreter that's already set up,
+// so things like args or --max-steps can be set before running
+pub fn run_source_with(interpreter: &mut Interpreter, src: &str) -> Result<(), AsplError> {
+    let ast = parse_source(src)?;
+
+    interpreter.run(&ast)?;
+
+    Ok(())
+}
+
+// Lexes and parses src in one pass, the parser pulls each token from
+// the lexer as it needs it rather than the whole file being lexed first.
+// A lexer error ends the token stream early, so it's reported unless the
+// parser already failed on a token before it.
+pub fn parse_source(src: &str) -> Result<Vec<Node>, AsplError> {
+    let mut lexer_error = None;
+
+    let tokens = Lexer::new(src.chars()).map_while(|token| match token {
+        Ok(token) => Some(token),
+        Err(err) => {
+            lexer_error = Some(err);
+            None
+        }
+    });
+
+    let ast = Parser::new(tokens).parse();
+
+    match (ast, lexer_error) {
+        (Err(err), Some(lexer_err)) if err.token.as_ref()
+            .is_some_and(|token| (token.line, token.col) < (lexer_err.line, lexer_err.col)) => Err(AsplError::Parser(err)),
+        (_, Some(lexer_err)) => Err(AsplError::Lexer(lexer_err)),
+        (ast, None) => Ok(ast?)
+    }
+}
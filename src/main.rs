@@ -1,16 +1,134 @@
-mod lexer;
-mod parser;
-mod interpreter;
-use std::{env, fs, path::PathBuf, process::exit};
+use std::{env, fs, path::PathBuf, process::exit, time::Duration};
 use inline_colorization::*;
-use interpreter::Interpreter;
-use lexer::Lexer;
-use parser::Parser;
+use aspl::{run_source_with, AsplError, Interpreter};
+
+// Prints an error as a single line of json, for editors and other tools
+fn print_json_error(phase: &str, r#type: &str, message: &str, position: Option<(usize, usize)>, backtrace: &[String]) {
+    fn escape(str: &str) -> String {
+        let mut escaped = String::new();
+
+        for char in str.chars() {
+            match char {
+                '"'     => escaped.push_str("\\\""),
+                '\\'    => escaped.push_str("\\\\"),
+                '\n'    => escaped.push_str("\\n"),
+                '\r'    => escaped.push_str("\\r"),
+                '\t'    => escaped.push_str("\\t"),
+                char if char.is_control() => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+                char    => escaped.push(char)
+            }
+        }
+
+        escaped
+    }
+
+    let (line, col) = match position {
+        Some((line, col)) => (line.to_string(), col.to_string()),
+        None => ("null".to_string(), "null".to_string())
+    };
+
+    let backtrace = backtrace.iter()
+        .map(|name| format!("\"{}\"", escape(name)))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    println!(
+        "{{\"phase\":\"{}\",\"type\":\"{}\",\"message\":\"{}\",\"line\":{},\"col\":{},\"backtrace\":[{}]}}",
+        escape(phase),
+        escape(r#type),
+        escape(message),
+        line,
+        col,
+        backtrace
+    );
+}
+
+// Prints the functions an error went through, innermost first,
+// with recursive calls of the same function folded into one line
+fn print_backtrace(backtrace: &[String]) {
+    if backtrace.is_empty() {
+        return;
+    }
+
+    println!("{color_yellow}[BACKTRACE]{color_reset} ->");
+
+    let mut frames = backtrace.iter().peekable();
+
+    while let Some(name) = frames.next() {
+        let mut repeated = 1;
+
+        while frames.next_if(|next| *next == name).is_some() {
+            repeated += 1;
+        }
+
+        match repeated {
+            1 => println!("    in @{}", name),
+            _ => println!("    in @{} ({} times)", name, repeated)
+        }
+    }
+}
+
+const USAGE: &str = "$ aspl [--bool-math] [--max-steps N] [--timeout MS] [--json-errors] [--version] <input.aspl> [args...]";
+
+#[derive(Default)]
+struct Options {
+    version: bool,
+    bool_math: bool,
+    json_errors: bool,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    source: Option<String>,
+    script_args: Vec<String>,
+}
+
+// Flags go before the file, anything after the file is left to the script
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            options.source = Some(arg);
+            options.script_args = args.collect();
+
+            break;
+        }
+
+        match arg.as_str() {
+            "--version" | "-V"  => options.version = true,
+            "--bool-math"       => options.bool_math = true,
+            "--json-errors"     => options.json_errors = true,
+            "--max-steps"       => match args.next().and_then(|steps| steps.parse().ok()) {
+                Some(steps) => options.max_steps = Some(steps),
+                None => return Err("--max-steps expects a number".to_string())
+            },
+            "--timeout"         => match args.next().and_then(|ms| ms.parse().ok()) {
+                Some(ms) => options.timeout = Some(Duration::from_millis(ms)),
+                None => return Err("--timeout expects a number of milliseconds".to_string())
+            },
+            _ => return Err(format!("Unknown flag: {}", arg))
+        }
+    }
+
+    Ok(options)
+}
 
 fn main() {
-    let mut args = env::args().skip(1);
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(message) => {
+            println!("{color_red}[ERROR]{color_reset} -> {}.", message);
+            println!("{color_green}[USAGE]{color_reset} -> {}", USAGE);
+            exit(1);
+        }
+    };
+
+    if options.version {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        exit(0);
+    }
 
-    let (source_path, source_parent) = match args.next() {
+    let (source_path, source_parent) = match options.source {
         Some(arg) if !arg.ends_with(".aspl") => {
             println!("{color_red}[ERROR]{color_reset} -> Invalid file extension.");
             exit(1);
@@ -28,7 +146,7 @@ fn main() {
         },
         None => {
             println!("{color_red}[ERROR]{color_reset} -> Specify the aspl file:");
-            println!("{color_green}[USAGE]{color_reset} -> $ aspl <input.aspl>");
+            println!("{color_green}[USAGE]{color_reset} -> {}", USAGE);
             exit(1);
         }
     };
@@ -55,9 +173,19 @@ fn main() {
         }
     };
 
-    let tokens = match Lexer::new(source.as_str().chars()).lex() {
-        Ok(tokens) => tokens,
-        Err(err) => {
+    let mut interpreter = Interpreter::new(cwd.join(&source_parent));
+    interpreter.set_args(options.script_args);
+    interpreter.set_bool_math(options.bool_math);
+    interpreter.set_fuel(options.max_steps);
+    interpreter.set_timeout(options.timeout);
+
+    match run_source_with(&mut interpreter, &source) {
+        Ok(()) => (),
+        Err(err) if options.json_errors => {
+            print_json_error(err.phase(), &err.kind(), err.message(), err.position(), interpreter.backtrace());
+            exit(1);
+        },
+        Err(AsplError::Lexer(err)) => {
             println!("{color_red}[ERROR]{color_reset} -> Lexing Error: {}.", err.message);
 
             if let Some(char) = err.char {
@@ -65,14 +193,8 @@ fn main() {
             }
 
             exit(1);
-        }
-    };
-
-    // println!("{:#?}", tokens);
-
-    let ast = match Parser::new(tokens.iter().cloned().into_iter()).parse() {
-        Ok(ast) => ast,
-        Err(err) => {
+        },
+        Err(AsplError::Parser(err)) => {
             println!("{color_red}[ERROR]{color_reset} -> Parsing Error: {}.", err.message);
 
             if let Some(token) = err.token {
@@ -81,14 +203,16 @@ fn main() {
             }
 
             exit(1);
-        }
-    };
+        },
+        Err(AsplError::Interpreter(err)) => {
+            println!("{color_red}[ERROR]{color_reset} -> {:?}: {}.", err.r#type, err.message);
 
-    // println!("{:#?}", ast);
+            if let Some((line, col)) = err.position {
+                println!("{color_yellow}[POSITION]{color_reset} -> {}:{}", line, col);
+            }
 
-    let mut interpreter = Interpreter::new(cwd.clone());
-    if let Err(err) = interpreter.run(&ast) {
-        println!("{color_red}[ERROR]{color_reset} -> {:?}: {}.", err.r#type, err.message);
-        exit(1);
+            print_backtrace(interpreter.backtrace());
+            exit(1);
+        }
     }
 }
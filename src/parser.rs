@@ -1,13 +1,40 @@
-use crate::lexer::{Lexer, Token, TokenTypes};
-use inline_colorization::*;
-use std::{env, fs, mem::discriminant, path::{Path, PathBuf}};
+use crate::{lexer::{Token, TokenTypes}, symbol::Symbol};
+use std::{mem::discriminant, ops::Deref};
+
+// Every @ builtin handled by parse_function_call
+pub const BUILTINS: &[&str] = &[
+    "source", "math", "rand",
+    "args", "arg", "exit",
+    "read_key", "color", "colorl",
+    "format", "parse_csv", "to_csv",
+    "clear", "move_cursor", "abs_path",
+    "fixed", "clamp", "abs_diff",
+    "count", "replace", "trim",
+    "trim_start", "trim_end", "flatten",
+    "first", "last", "take", "drop",
+    "deepcopy", "debug", "sum",
+    "product", "avg", "if",
+    "input_int", "pad_left", "pad_right",
+    "enumerate", "chars", "factorial",
+    "gcd", "lcm", "is_prime",
+    "exists", "is_file", "is_dir",
+    "lines", "starts_with", "ends_with",
+    "error", "min_by", "max_by",
+    "find_from", "choice", "record",
+    "capitalize", "title_case", "zfill",
+    "round_to", "empty", "memoize",
+    "bench", "clone"
+];
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Literals {
     String(String),
     Int(i64),
     Boolean(bool),
-    Array(Vec<Literals>)
+    None,
+    Array(Vec<Literals>),
+    // A name inside an array literal, looked up when the array is built
+    Identifier(Symbol)
 }
 
 impl Literals {
@@ -16,7 +43,9 @@ impl Literals {
             Literals::Int(_) => "int",
             Literals::String(_) => "string",
             Literals::Boolean(_) => "boolean",
-            Literals::Array(_) => "array"
+            Literals::None => "none",
+            Literals::Array(_) => "array",
+            Literals::Identifier(_) => "identifier"
         }
     }
 }
@@ -24,13 +53,27 @@ impl Literals {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Node {
     Literal(Literals),
-    Identifier(String),
+    Identifier(Symbol),
     Return(Box<Node>),
     Break,
+    // A statement along with the line and col it starts at
+    Statement {
+        line: usize,
+        col: usize,
+        node: Box<Node>
+    },
     Var {
         identifier: Box<Node>,
         value: Box<Node>
     },
+    Destructure {
+        targets: Vec<Node>,
+        value: Box<Node>
+    },
+    Swap {
+        left: Box<Node>,
+        right: Box<Node>
+    },
     ArrayAccess {
         identifier: Box<Node>,
         index: Box<Node>
@@ -40,6 +83,12 @@ pub enum Node {
         condition: String,
         right: Box<Node>
     },
+    LogicalExpr {
+        left: Box<Node>,
+        op: String,
+        right: Box<Node>
+    },
+    Not(Box<Node>),
     MathExpr {
         left: Box<Node>,
         op: String,
@@ -49,9 +98,159 @@ pub enum Node {
         start: Box<Node>,
         end: Box<Node>
     },
+    Args,
+    Arg(Box<Node>),
+    Exit(Box<Node>),
+    Raise(Box<Node>),
+    ReadKey,
+    InputInt {
+        prompt: Option<Box<Node>>
+    },
+    Color {
+        value: Box<Node>,
+        color: Box<Node>
+    },
+    ColorLog {
+        value: Box<Node>,
+        color: Box<Node>
+    },
+    Format {
+        template: Box<Node>,
+        args: Vec<Box<Node>>
+    },
+    ParseCsv(Box<Node>),
+    ToCsv(Box<Node>),
+    AbsPath(Box<Node>),
+    Exists(Box<Node>),
+    IsFile(Box<Node>),
+    IsDir(Box<Node>),
+    Fixed {
+        value: Box<Node>,
+        decimals: Box<Node>
+    },
+    Clamp {
+        value: Box<Node>,
+        lo: Box<Node>,
+        hi: Box<Node>
+    },
+    AbsDiff {
+        left: Box<Node>,
+        right: Box<Node>
+    },
+    RoundTo {
+        value: Box<Node>,
+        multiple: Box<Node>
+    },
+    Gcd {
+        left: Box<Node>,
+        right: Box<Node>
+    },
+    Lcm {
+        left: Box<Node>,
+        right: Box<Node>
+    },
+    StartsWith {
+        value: Box<Node>,
+        prefix: Box<Node>
+    },
+    EndsWith {
+        value: Box<Node>,
+        suffix: Box<Node>
+    },
+    MinBy {
+        function: Box<Node>,
+        array: Box<Node>
+    },
+    MaxBy {
+        function: Box<Node>,
+        array: Box<Node>
+    },
+    FindFrom {
+        array: Box<Node>,
+        value: Box<Node>,
+        start: Box<Node>
+    },
+    Count {
+        haystack: Box<Node>,
+        needle: Box<Node>
+    },
+    Replace {
+        source: Box<Node>,
+        from: Box<Node>,
+        to: Box<Node>
+    },
+    ZFill {
+        number: Box<Node>,
+        width: Box<Node>
+    },
+    PadLeft {
+        value: Box<Node>,
+        width: Box<Node>,
+        fill: Option<Box<Node>>
+    },
+    PadRight {
+        value: Box<Node>,
+        width: Box<Node>,
+        fill: Option<Box<Node>>
+    },
+    Trim(Box<Node>),
+    TrimStart(Box<Node>),
+    TrimEnd(Box<Node>),
+    Flatten {
+        array: Box<Node>,
+        depth: Option<Box<Node>>
+    },
+    First(Box<Node>),
+    Last(Box<Node>),
+    Take {
+        array: Box<Node>,
+        count: Box<Node>
+    },
+    Drop {
+        array: Box<Node>,
+        count: Box<Node>
+    },
+    DeepCopy(Box<Node>),
+    Clone(Box<Node>),
+    Enumerate(Box<Node>),
+    Chars(Box<Node>),
+    Empty(Box<Node>),
+    Memoize(Box<Node>),
+    Bench {
+        value: Box<Node>,
+        count: Option<Box<Node>>
+    },
+    Capitalize(Box<Node>),
+    TitleCase(Box<Node>),
+    Record(Vec<(Node, Node)>),
+    Choice(Box<Node>),
+    Lines(Box<Node>),
+    Factorial(Box<Node>),
+    IsPrime(Box<Node>),
+    Debug(Box<Node>),
+    IfExpr {
+        cond: Box<Node>,
+        then_val: Box<Node>,
+        else_val: Box<Node>
+    },
+    Sum(Box<Node>),
+    Product(Box<Node>),
+    Avg(Box<Node>),
+    Clear,
+    MoveCursor {
+        row: Box<Node>,
+        col: Box<Node>
+    },
     Scope {
         body: Vec<Box<Node>>
     },
+    // A function arg that falls back to default when left out
+    DefaultArg {
+        identifier: Box<Node>,
+        default: Box<Node>
+    },
+    // ...rest, gets the args left over as an array
+    RestArg(Box<Node>),
     Function {
         identifier: Box<Node>,
         args: Vec<Box<Node>>,
@@ -63,8 +262,7 @@ pub enum Node {
     },
     Source {
         file_name: String,
-        cwd: PathBuf,
-        ast: Vec<Node>
+        alias: Option<Symbol>
     },
 
     // Statements
@@ -74,15 +272,49 @@ pub enum Node {
     },
     Update {
         identifier: Box<Node>,
-        value: Box<Node>
+        value: Box<Node>,
+        global: bool
     },
     Check {
         condition: Box<Node>,
         scope: Box<Node>
     },
+    Try {
+        body: Box<Node>,
+        catch_var: Box<Node>,
+        catch_scope: Box<Node>
+    },
     While {
         condition: Box<Node>,
-        scope: Box<Node>
+        scope: Box<Node>,
+        else_scope: Option<Box<Node>>
+    },
+    Require {
+        condition: Box<Node>,
+        message: Box<Node>
+    },
+    Match {
+        value: Box<Node>,
+        arms: Vec<(Node, Node)>,
+        default: Option<Box<Node>>
+    },
+    Inc {
+        identifier: Box<Node>,
+        step: Box<Node>
+    },
+    Dec {
+        identifier: Box<Node>,
+        step: Box<Node>
+    }
+}
+
+impl Node {
+    pub fn is_break(&self) -> bool {
+        match self {
+            Node::Break => true,
+            Node::Statement { node, .. } => node.is_break(),
+            _ => false
+        }
     }
 }
 
@@ -92,34 +324,45 @@ pub struct ParserError {
     pub token: Option<Token>,
 }
 
-type ParserResult<T> = Result<T, ParserError>;
+pub type ParserResult<T> = Result<T, ParserError>;
 
 #[derive(Debug, Clone)]
-pub struct Parser<T: Iterator<Item = Token> + Clone> {
+pub struct Parser<T: Iterator<Item = Token>> {
     tokens: T,
-    current_token: Option<Token>
+    current_token: Option<Token>,
+    // Pulled one ahead of current_token, so peek doesn't need &mut
+    next_token: Option<Token>
 }
 
-impl<T: Iterator<Item = Token> + Clone> Parser<T> {
+impl<T: Iterator<Item = Token>> Parser<T> {
     pub fn new(mut tokens: T) -> Self {
         let current_token = tokens.next();
+        let next_token = tokens.next();
 
         Self {
             tokens,
-            current_token
+            current_token,
+            next_token
         }
     }
 
     fn parse_set_statement(&mut self) -> ParserResult<Node> {
         self.advance();
 
-        let identifier = self.parse_identifier()?;
+        // set [a b c] arr binds each name to the element at its position
+        let destructure = matches!(&self.current_token, Some(token) if token.r#type.is_open_bracket());
+
+        let mut targets = match destructure {
+            true    => self.parse_destructure_targets()?,
+            false   => vec![self.parse_identifier()?]
+        };
 
         let value = match &self.current_token.clone() {
             Some(node) => match node {
                 node if node.r#type.is_literal() ||
                         node.r#type.is_open_bracket()   => self.parse_literal()?,
                 node if node.r#type.is_fn_call()        => self.parse_function_call()?,
+                node if node.r#type.is_open_paren()     => self.parse_group()?,
                 node if node.r#type.is_identifier() &&
                         self.peek().is_some() &&
                         self.peek().unwrap()
@@ -143,15 +386,58 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
             }
         };
 
+        if destructure {
+            return Ok(Node::Destructure {
+                targets,
+                value: Box::new(value),
+            });
+        }
+
         Ok(Node::Var {
-            identifier: Box::new(identifier),
+            identifier: Box::new(targets.remove(0)),
             value: Box::new(value),
         })
     }
 
+    fn parse_destructure_targets(&mut self) -> ParserResult<Vec<Node>> {
+        self.advance();
+
+        let mut targets: Vec<Node> = vec![];
+
+        while let Some(token) = &self.current_token {
+            if token.r#type.is_close_bracket() {
+                self.advance();
+                return Ok(targets);
+            }
+
+            if token.r#type.is_comma() {
+                self.advance();
+                continue;
+            }
+
+            targets.push(self.parse_identifier()?);
+        }
+
+        Err(ParserError {
+            message: format!("Unexpected end of input while parsing set targets"),
+            token: None,
+        })
+    }
+
     fn parse_update_statement(&mut self) -> ParserResult<Node> {
         self.advance();
 
+        // update global name value, targets the outermost binding.
+        // global is a keyword, so it can't be a variable's name
+        let global = match &self.current_token {
+            Some(token) if token.r#type.is_statement() &&
+                           token.value.as_deref() == Some("global") => {
+                self.advance();
+                true
+            },
+            _ => false
+        };
+
         let identifier = self.parse_identifier()?;
 
         let value = match &self.current_token.clone() {
@@ -160,6 +446,7 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
                         node.r#type.is_open_bracket()   => self.parse_literal()?,
                 node if node.r#type.is_identifier()     => self.parse_identifier()?,
                 node if node.r#type.is_fn_call()        => self.parse_function_call()?,
+                node if node.r#type.is_open_paren()     => self.parse_group()?,
                 _ => {
                     return Err(ParserError {
                         message: format!(
@@ -181,15 +468,21 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
         Ok(Node::Update {
             identifier: Box::new(identifier),
             value: Box::new(value),
+            global,
         })
     }
 
     fn parse_log_statement(&mut self, statement: String) -> ParserResult<Node> {
+        let line = self.current_token.as_ref().map(|token| token.line).unwrap_or_default();
+
         self.advance();
 
         let mut args: Vec<Box<Node>> = vec![];
 
         while let Some(arg) = &self.current_token {
+            // Like fn call args, a call or (group) on the next line starts a new statement
+            let same_line = arg.line == line;
+
             match arg.r#type {
                 arg if arg.is_literal() ||
                         arg.is_open_bracket()           => args.push(Box::new(self.parse_literal()?)),
@@ -198,19 +491,24 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
                         self.peek().unwrap()
                             .r#type.is_open_bracket()   => args.push(Box::new(self.parse_array_access()?)),
                 arg if arg.is_identifier()              => args.push(Box::new(self.parse_identifier()?)),
-                arg if arg.is_fn_call()                 => args.push(Box::new(self.parse_function_call()?)),
+                arg if arg.is_fn_call() && same_line    => args.push(Box::new(self.parse_function_call()?)),
+                arg if arg.is_open_paren() && same_line => args.push(Box::new(self.parse_group()?)),
                 _ => break,
             }
         }
 
-        if args.is_empty() {
-            return Err(ParserError {
-                message: format!(
-                    "Unexpected end of input while parsing {} statement",
-                    statement
-                ),
-                token: None,
-            });
+        // A bare log is fine as long as nothing dangles after it on its line
+        if let Some(token) = &self.current_token {
+            if args.is_empty() && token.line == line && !token.r#type.is_statement() && !token.r#type.is_close_curly() {
+                return Err(ParserError {
+                    message: format!(
+                        "Expected a value on {} statement, but found {:?}",
+                        statement,
+                        token.r#type
+                    ),
+                    token: Some(token.clone()),
+                });
+            }
         }
 
         Ok(Node::Log {
@@ -222,78 +520,181 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
     fn parse_check_statement(&mut self) -> ParserResult<Node> {
         self.advance();
 
-        if let Some(token) = &self.current_token {
-            if token.r#type.is_literal() || token.r#type.is_identifier() {
-                if let Some(token) = self.peek() {
-                    if token.r#type.is_condition_op() {
-                        let condition = self.parse_condition()?;
-                        let scope = self.parse_scope()?;
-
-                        return Ok(Node::Check {
-                            condition: Box::new(condition),
-                            scope: Box::new(scope),
-                        });
-                    }
-                }
-            }
+        if self.current_token.is_none() {
+            return Err(ParserError {
+                message: format!("Unexpected end of input while parsing check statement"),
+                token: None,
+            });
+        }
 
-            if token.r#type.is_literal() {
-                let literal = self.parse_literal()?;
-                let scope = self.parse_scope()?;
+        let condition = self.parse_condition()?;
+        let scope = self.parse_scope()?;
 
-                return Ok(Node::Check {
-                    condition: Box::new(literal),
-                    scope: Box::new(scope),
-                });
-            }
+        Ok(Node::Check {
+            condition: Box::new(condition),
+            scope: Box::new(scope),
+        })
+    }
+
+    fn parse_try_statement(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let body = self.parse_scope()?;
+
+        match &self.current_token {
+            Some(token) if token.r#type.is_statement() &&
+                           token.value.as_deref() == Some("catch") => self.advance(),
+            Some(token) => return Err(ParserError {
+                message: format!("Expected catch after try, but found {:?}", token.r#type),
+                token: Some(token.clone()),
+            }),
+            None => return Err(ParserError {
+                message: format!("Unexpected end of input while parsing try statement"),
+                token: None,
+            })
         }
 
-        Err(ParserError {
-            message: format!("Unexpected end of input while parsing check statement"),
-            token: None,
+        let catch_var = self.parse_identifier()?;
+        let catch_scope = self.parse_scope()?;
+
+        Ok(Node::Try {
+            body: Box::new(body),
+            catch_var: Box::new(catch_var),
+            catch_scope: Box::new(catch_scope),
         })
     }
 
     fn parse_while_statement(&mut self) -> ParserResult<Node> {
         self.advance();
 
-        if let Some(token) = &self.current_token {
-            if token.r#type.is_literal() || token.r#type.is_identifier() {
-                if let Some(condition) = self.peek() {
-                    if condition.r#type.is_condition_op() || condition.r#type.is_open_bracket() {
-                        let condition = self.parse_condition()?;
-                        let scope = self.parse_scope()?;
+        if self.current_token.is_none() {
+            return Err(ParserError {
+                message: format!("Unexpected end of input while parsing while statement"),
+                token: None,
+            });
+        }
 
-                        return Ok(Node::While {
-                            condition: Box::new(condition),
-                            scope: Box::new(scope),
-                        });
-                    }
-                }
-            }
+        let condition = self.parse_condition()?;
+        let scope = self.parse_scope()?;
+        let else_scope = self.parse_else_scope()?;
 
-            if token.r#type.is_literal() || token.r#type.is_open_bracket() {
-                let literal = self.parse_literal()?;
-                let scope = self.parse_scope()?;
+        Ok(Node::While {
+            condition: Box::new(condition),
+            scope: Box::new(scope),
+            else_scope,
+        })
+    }
+
+    fn parse_else_scope(&mut self) -> ParserResult<Option<Box<Node>>> {
+        match &self.current_token {
+            Some(token) if token.r#type.is_identifier() &&
+                           token.value.as_deref() == Some("else") => {
+                self.advance();
+
+                Ok(Some(Box::new(self.parse_scope()?)))
+            },
+            _ => Ok(None)
+        }
+    }
+
+    fn parse_require_statement(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let condition = self.parse_condition()?;
+        let message = self.parse_value("require")?;
 
-                return Ok(Node::While {
-                    condition: Box::new(literal),
-                    scope: Box::new(scope),
+        Ok(Node::Require {
+            condition: Box::new(condition),
+            message: Box::new(message),
+        })
+    }
+
+    fn parse_inc_statement(&mut self, statement: String) -> ParserResult<Node> {
+        self.advance();
+
+        let identifier = self.parse_identifier()?;
+
+        let step = match &self.current_token {
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_identifier() => self.parse_value(&statement)?,
+            _ => Node::Literal(Literals::Int(1))
+        };
+
+        if statement == "dec" {
+            return Ok(Node::Dec {
+                identifier: Box::new(identifier),
+                step: Box::new(step),
+            });
+        }
+
+        Ok(Node::Inc {
+            identifier: Box::new(identifier),
+            step: Box::new(step),
+        })
+    }
+
+    fn parse_match_statement(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("match")?;
+
+        match &self.current_token {
+            Some(token) if token.r#type.is_open_curly() => self.advance(),
+            Some(token) => return Err(ParserError {
+                message: format!("Expected open curly on match, but found {:?}", token.r#type),
+                token: Some(token.clone()),
+            }),
+            None => return Err(ParserError {
+                message: format!("Unexpected end of input while parsing match statement"),
+                token: None,
+            })
+        }
+
+        let mut arms: Vec<(Node, Node)> = vec![];
+        let mut default: Option<Box<Node>> = None;
+
+        while let Some(token) = &self.current_token.clone() {
+            if token.r#type.is_close_curly() {
+                self.advance();
+
+                return Ok(Node::Match {
+                    value: Box::new(value),
+                    arms,
+                    default,
                 });
             }
 
-            return Err(ParserError {
-                message: format!("Expected a condition or literal, but found {:?}", token),
-                token: Some(token.clone()),
-            });
+            if token.r#type.is_identifier() && token.value.as_deref() == Some("_") {
+                self.advance();
+                default = Some(Box::new(self.parse_scope()?));
+
+                continue;
+            }
+
+            let pattern = self.parse_literal()?;
+            let scope = self.parse_scope()?;
+
+            arms.push((pattern, scope));
         }
 
         Err(ParserError {
-            message: format!("Unexpected end of input while parsing while statement"),
+            message: format!("Unexpected end of input while parsing match statement"),
             token: None,
         })
     }
 
+    fn parse_swap_statement(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let left = self.parse_identifier()?;
+        let right = self.parse_identifier()?;
+
+        Ok(Node::Swap {
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+    }
+
     fn parse_break(&mut self) -> ParserResult<Node> {
         self.advance();
 
@@ -306,14 +707,53 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
         let identifier = self.parse_identifier()?;
         let mut args: Vec<Box<Node>> = vec![];
 
-        while let Some(token) = &self.current_token {
+        while let Some(token) = &self.current_token.clone() {
+            if token.r#type.is_ellipsis() {
+                self.advance();
+
+                let rest = self.parse_identifier()?;
+                args.push(Box::new(Node::RestArg(Box::new(rest))));
+
+                if let Some(token) = &self.current_token {
+                    if token.r#type.is_identifier() || token.r#type.is_ellipsis() {
+                        return Err(ParserError {
+                            message: format!("Expected the rest arg to be the last arg, but found {:?}", token.value.clone().unwrap_or_default()),
+                            token: Some(token.clone()),
+                        });
+                    }
+                }
+
+                break;
+            }
+
             if !token.r#type.is_identifier() {
                 break;
             }
 
-            if let Ok(arg) = self.parse_identifier() {
-                args.push(Box::new(arg));
+            let arg = self.parse_identifier()?;
+
+            // greeting="Hello", only trailing args can have a default
+            if self.current_token.as_ref().is_some_and(|token| token.r#type.is_eq()) {
+                self.advance();
+
+                let default = self.parse_literal()?;
+
+                args.push(Box::new(Node::DefaultArg {
+                    identifier: Box::new(arg),
+                    default: Box::new(default),
+                }));
+
+                continue;
+            }
+
+            if args.iter().any(|arg| matches!(arg.deref(), Node::DefaultArg { .. })) {
+                return Err(ParserError {
+                    message: format!("Expected a default for {:?}, args after a defaulted one need defaults too", token.value.clone().unwrap_or_default()),
+                    token: Some(token.clone()),
+                });
             }
+
+            args.push(Box::new(arg));
         }
 
         let scope = self.parse_scope()?;
@@ -344,6 +784,11 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
                 return Ok(Node::Return(Box::new(ret_fn_call)));
             }
 
+            if token.r#type.is_open_paren() {
+                let ret_group = self.parse_group()?;
+                return Ok(Node::Return(Box::new(ret_group)));
+            }
+
             return Err(ParserError {
                 message: format!(
                     "Expected a literal/identifier/fn_call, but found {:?}",
@@ -367,12 +812,18 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
             match statement.as_str() {
                 "set"           => return self.parse_set_statement(),
                 "update"        => return self.parse_update_statement(),
-                "log" | "logl"  => return self.parse_log_statement(statement),
+                "log"  | "logl" |
+                "elog" | "elogl" => return self.parse_log_statement(statement),
                 "check"         => return self.parse_check_statement(),
                 "while"         => return self.parse_while_statement(),
+                "try"           => return self.parse_try_statement(),
                 "fn"            => return self.parse_function(),
                 "ret"           => return self.parse_return(),
                 "break"         => return self.parse_break(),
+                "require"       => return self.parse_require_statement(),
+                "match"         => return self.parse_match_statement(),
+                "inc" | "dec"   => return self.parse_inc_statement(statement),
+                "swap"          => return self.parse_swap_statement(),
 
                 _ => {
                     return Err(ParserError {
@@ -400,15 +851,23 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
                 break;
             }
 
+            // Commas between values are optional
+            if token.r#type.is_comma() {
+                self.advance();
+                continue;
+            }
+
             let value: Literals = match token.r#type {
                 TokenTypes::IntLiteral      => Literals::Int(token.value.clone().unwrap().parse().unwrap()),
                 TokenTypes::StringLiteral   => Literals::String(token.value.clone().unwrap().parse().unwrap()),
                 TokenTypes::BooleanLiteral  => Literals::Boolean(token.value.clone().unwrap().parse().unwrap()),
+                TokenTypes::NoneLiteral     => Literals::None,
                 TokenTypes::OpenBracket     => self.parse_array_literal()?,
+                TokenTypes::Identifier      => Literals::Identifier(Symbol::intern(token.value.as_deref().unwrap())),
                 _ => {
                     return Err(ParserError {
                         message: format!(
-                            "Expected a literal, but found {:?}",
+                            "Expected a literal or identifier, but found {:?}",
                             token.r#type
                         ),
                         token: Some(self.current_token.clone().unwrap()),
@@ -419,29 +878,76 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
             if matches!(value,
                 Literals::Int(_) |
                 Literals::String(_) |
-                Literals::Boolean(_)) {
+                Literals::Boolean(_) |
+                Literals::None |
+                Literals::Identifier(_)) {
                 self.advance();
             }
 
             values.push(value);
         }
 
-        if !values.iter().all(|value| discriminant(value) == discriminant(&values[0])) {
+        let elements = values.iter()
+            .enumerate()
+            .map(|(i, value)| (format!("[{}]", i), value))
+            .collect();
+
+        if let Err(message) = Self::check_array_types(elements) {
             return Err(ParserError {
-                message: format!("Cannot have two or more types in array"),
-                token: Some(self.current_token.clone().unwrap()),
+                message,
+                token: self.current_token.clone(),
             });
         }
 
         Ok(Literals::Array(values))
     }
 
+    // Checks that every element on the same depth has the same type,
+    // so [[1 2] ["a"]] is caught even though both elements are arrays.
+    // Empty arrays have no element type to check against.
+    fn check_array_types(elements: Vec<(String, &Literals)>) -> Result<(), String> {
+        // Names have no type until they're looked up, the interpreter checks those
+        let elements: Vec<(String, &Literals)> = elements.into_iter()
+            .filter(|(_, value)| !matches!(value, Literals::Identifier(_)))
+            .collect();
+
+        let (first_path, first) = match elements.first() {
+            Some(first) => first,
+            None => return Ok(())
+        };
+
+        for (path, value) in &elements {
+            if discriminant(*value) != discriminant(*first) {
+                return Err(format!(
+                    "Cannot have two or more types in array, found {} at {} and {} at {}",
+                    first.name(),
+                    first_path,
+                    value.name(),
+                    path
+                ));
+            }
+        }
+
+        let mut children: Vec<(String, &Literals)> = vec![];
+
+        for (path, value) in &elements {
+            if let Literals::Array(values) = value {
+                for (i, value) in values.iter().enumerate() {
+                    children.push((format!("{}[{}]", path, i), value));
+                }
+            }
+        }
+
+        Self::check_array_types(children)
+    }
+
     fn parse_literal(&mut self) -> ParserResult<Node> {
         if let Some(token) = &self.current_token.clone() {
             let value: Literals = match token.r#type {
                 TokenTypes::IntLiteral      => Literals::Int(token.value.clone().unwrap().parse().unwrap()),
                 TokenTypes::StringLiteral   => Literals::String(token.value.clone().unwrap().parse().unwrap()),
                 TokenTypes::BooleanLiteral  => Literals::Boolean(token.value.clone().unwrap().parse().unwrap()),
+                TokenTypes::NoneLiteral     => Literals::None,
                 TokenTypes::OpenBracket     => self.parse_array_literal()?,
                 _ => return Err(ParserError {
                     message: format!("Expected a literal, but found {:?}", token.r#type),
@@ -452,7 +958,8 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
             if matches!(value,
                 Literals::Int(_) |
                 Literals::String(_) |
-                Literals::Boolean(_)) {
+                Literals::Boolean(_) |
+                Literals::None) {
                 self.advance();
             }
 
@@ -476,7 +983,7 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
 
             self.advance();
 
-            return Ok(Node::Identifier(token.value.clone().unwrap()));
+            return Ok(Node::Identifier(Symbol::intern(token.value.as_deref().unwrap())));
         }
 
         Err(ParserError {
@@ -486,8 +993,13 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
     }
 
     fn parse_array_access(&mut self) -> ParserResult<Node> {
-        let mut current_identifier = self.parse_identifier()?;
+        let identifier = self.parse_identifier()?;
+
+        self.parse_indexes(identifier)
+    }
 
+    // Wraps the node in an ArrayAccess for every [index] that follows it
+    fn parse_indexes(&mut self, mut current_identifier: Node) -> ParserResult<Node> {
         while let Some(token) = &self.current_token {
             if !token.r#type.is_open_bracket() {
                 break;
@@ -498,6 +1010,15 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
             let index = match &self.current_token {
                 Some(token) if token.r#type.is_literal()        => self.parse_literal()?,
                 Some(token) if token.r#type.is_identifier()     => self.parse_identifier()?,
+                Some(token) if token.r#type == TokenTypes::Sub &&
+                               self.peek().is_some_and(|next| next.r#type == TokenTypes::IntLiteral) => {
+                    self.advance();
+
+                    match self.parse_literal()? {
+                        Node::Literal(Literals::Int(index)) => Node::Literal(Literals::Int(-index)),
+                        _ => unreachable!()
+                    }
+                },
                 Some(token) => return Err(ParserError {
                     message: format!("Expected an index, but found {:?}", token.r#type),
                     token: None,
@@ -533,30 +1054,113 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
         Ok(current_identifier)
     }
 
+    // Conditions are combined with || and &&, with && binding tighter.
+    // Each side can be a comparison, a literal, a !negation or a (group).
     fn parse_condition(&mut self) -> ParserResult<Node> {
-        let left = match &self.current_token {
-            Some(left) => match left {
-                node if node.r#type.is_identifier() &&
-                        self.peek().is_some() &&
-                        self.peek().unwrap()
-                            .r#type.is_open_bracket()   => self.parse_array_access()?,
-                left if left.r#type.is_identifier()     => self.parse_identifier()?,
-                left if left.r#type.is_literal()        => self.parse_literal()?,
-                left => {
-                    return Err(ParserError {
-                        message: format!("Expected a identifier or literal, but found {:?}", left),
-                        token: Some(left.clone()),
+        let mut left = self.parse_and_condition()?;
+
+        while let Some(token) = &self.current_token {
+            if !token.r#type.is_or() {
+                break;
+            }
+
+            self.advance();
+
+            let right = self.parse_and_condition()?;
+            left = Node::LogicalExpr {
+                left: Box::new(left),
+                op: "||".to_string(),
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_and_condition(&mut self) -> ParserResult<Node> {
+        let mut left = self.parse_condition_operand()?;
+
+        while let Some(token) = &self.current_token {
+            if !token.r#type.is_and() {
+                break;
+            }
+
+            self.advance();
+
+            let right = self.parse_condition_operand()?;
+            left = Node::LogicalExpr {
+                left: Box::new(left),
+                op: "&&".to_string(),
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_condition_operand(&mut self) -> ParserResult<Node> {
+        match &self.current_token {
+            Some(token) if token.r#type.is_not() => {
+                self.advance();
+
+                let operand = self.parse_condition_operand()?;
+                Ok(Node::Not(Box::new(operand)))
+            },
+            Some(token) if token.r#type.is_open_paren() => {
+                self.advance();
+
+                let condition = self.parse_condition()?;
+
+                match &self.current_token {
+                    Some(token) if token.r#type.is_close_paren() => self.advance(),
+                    Some(token) => return Err(ParserError {
+                        message: format!("Expected close parenthesis, but found {:?}", token.r#type),
+                        token: Some(token.clone()),
+                    }),
+                    None => return Err(ParserError {
+                        message: format!("Unexpected end of input while parsing condition"),
+                        token: None,
                     })
                 }
+
+                Ok(condition)
             },
-            None => {
-                return Err(ParserError {
-                    message: format!("Unexpected end of input while parsing condition"),
-                    token: None,
-                })
-            }
-        };
+            // arr[0] and plain variables, either compared or checked for truthiness
+            Some(token) if token.r#type.is_identifier() => {
+                let left = self.parse_array_access()?;
+                self.parse_optional_comparison(left)
+            },
+            // @has_next list, the result is compared or checked for truthiness
+            Some(token) if token.r#type.is_fn_call() => {
+                let left = self.parse_function_call()?;
+                self.parse_optional_comparison(left)
+            },
+            // Literals, including array literals like [1 2]
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_open_bracket() => {
+                let left = self.parse_literal()?;
+                self.parse_optional_comparison(left)
+            },
+            Some(token) => Err(ParserError {
+                message: format!("Expected a identifier or literal, but found {:?}", token),
+                token: Some(token.clone()),
+            }),
+            None => Err(ParserError {
+                message: format!("Unexpected end of input while parsing condition"),
+                token: None,
+            })
+        }
+    }
+
+    // Without a condition op after it, the left side stands on its own
+    fn parse_optional_comparison(&mut self, left: Node) -> ParserResult<Node> {
+        match &self.current_token {
+            Some(token) if token.r#type.is_condition_op() => self.parse_comparison_right(left),
+            _ => Ok(left)
+        }
+    }
 
+    fn parse_comparison_right(&mut self, left: Node) -> ParserResult<Node> {
         let condition = match &self.current_token {
             Some(token) => match token.r#type {
                 TokenTypes::EqEq    => "==",
@@ -589,7 +1193,9 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
                         self.peek().unwrap()
                             .r#type.is_open_bracket()   => self.parse_array_access()?,
                 right if right.r#type.is_identifier()   => self.parse_identifier()?,
-                right if right.r#type.is_literal()      => self.parse_literal()?,
+                right if right.r#type.is_literal() ||
+                         right.r#type.is_open_bracket() => self.parse_literal()?,
+                right if right.r#type.is_fn_call()      => self.parse_function_call()?,
                 right => {
                     return Err(ParserError {
                         message: format!("Expected a identifier or literal, but found {:?}", right),
@@ -616,7 +1222,9 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
         self.advance();
 
         let mut stack: Vec<Token> = vec![];
-        let mut tokens: Vec<Token> = vec![];
+        // Operands that need more than one token, like arr[0][1],
+        // are parsed here and travel along with their first token
+        let mut tokens: Vec<(Token, Option<Node>)> = vec![];
 
         let token = match &self.current_token {
             Some(token) if token.r#type.is_open_paren() => token.clone(),
@@ -648,20 +1256,28 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
                     });
                 }
                 stack.pop();
-                tokens.push(token.clone());
+                tokens.push((token.clone(), None));
                 self.advance();
 
                 continue;
             }
 
+            if token.r#type.is_identifier() && self.peek().is_some_and(|token| token.r#type.is_open_bracket()) {
+                let token = token.clone();
+                let array_access = self.parse_array_access()?;
+                tokens.push((token, Some(array_access)));
+
+                continue;
+            }
+
             match token {
                 token if token.r#type.is_math_op() ||
                         token.r#type.is_literal()  ||
-                        token.r#type.is_identifier() => tokens.push(token.clone()),
+                        token.r#type.is_identifier() => tokens.push((token.clone(), None)),
 
                 token if token.r#type.is_open_paren() => {
                     stack.push(token.clone());
-                    tokens.push(token.clone());
+                    tokens.push((token.clone(), None));
                 }
 
                 _ => return Err(ParserError {
@@ -680,24 +1296,40 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
             });
         }
 
-        Ok(self.math_parse(tokens)?)
+        self.math_parse(tokens)
     }
 
-    fn math_parse(&mut self, tokens: Vec<Token>) -> ParserResult<Node> {
+    fn math_parse(&mut self, tokens: Vec<(Token, Option<Node>)>) -> ParserResult<Node> {
         let mut output_stack: Vec<Node> = vec![];
         let mut operator_stack: Vec<String> = vec![];
 
         let mut i = 0;
 
         while i < tokens.len() {
-            let token = &tokens[i];
+            let (token, operand) = &tokens[i];
+
+            if let Some(operand) = operand {
+                output_stack.push(operand.clone());
+                i += 1;
+
+                continue;
+            }
 
             match &token {
+                _ if matches!(token.r#type, TokenTypes::NoneLiteral) => {
+                    return Err(ParserError {
+                        message: "Cannot do math on none".to_string(),
+                        token: Some(token.clone()),
+                    });
+                },
+                token if matches!(token.r#type, TokenTypes::BooleanLiteral) => {
+                    output_stack.push(Node::Literal(Literals::Boolean(token.value.clone().unwrap().parse().unwrap())));
+                },
                 token if token.r#type.is_literal() => {
                     output_stack.push(Node::Literal(Literals::Int(token.value.clone().unwrap().parse().unwrap())));
                 },
                 token if token.r#type.is_identifier() => {
-                    output_stack.push(Node::Identifier(token.value.clone().unwrap()));
+                    output_stack.push(Node::Identifier(Symbol::intern(token.value.as_deref().unwrap())));
                 }
                 token if token.r#type.is_math_op() => {
                     let op = token.value.clone().unwrap();
@@ -830,221 +1462,1009 @@ impl<T: Iterator<Item = Token> + Clone> Parser<T> {
         })
     }
 
-    fn parse_scope(&mut self) -> ParserResult<Node> {
+    fn parse_args(&mut self) -> ParserResult<Node> {
         self.advance();
 
-        let mut body: Vec<Box<Node>> = vec![];
+        Ok(Node::Args)
+    }
 
-        while let Some(token) = &self.current_token {
-            if token.r#type.is_close_curly() {
-                self.advance();
-                break;
-            }
+    fn parse_arg(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-            let parsed_token = self.parse_token()?;
-            body.push(Box::new(parsed_token));
-        }
+        let index = self.parse_value("arg")?;
 
-        Ok(Node::Scope { body })
+        Ok(Node::Arg(Box::new(index)))
     }
 
-    fn parse_function_call(&mut self) -> ParserResult<Node> {
-        let identifier = match &self.current_token {
-            Some(token) => {
-                if let Some(fn_call_name) = &token.value {
-                    if fn_call_name == "source" {
-                        return self.parse_source();
-                    }
+    fn parse_raise(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-                    if fn_call_name == "math" {
-                        return self.parse_math_expr();
-                    }
+        let message = self.parse_value("error")?;
 
-                    if fn_call_name == "rand" {
-                        return self.parse_rand();
-                    }
-                }
+        Ok(Node::Raise(Box::new(message)))
+    }
 
-                Node::Identifier(token.clone().value.unwrap_or_default())
-            }
-            None => {
-                return Err(ParserError {
-                    message: format!("Unexpected end of input while parsing function call"),
-                    token: None,
-                })
-            }
+    fn parse_exit(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let code = match &self.current_token {
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_identifier() ||
+                           token.r#type.is_fn_call() => self.parse_value("exit")?,
+            _ => Node::Literal(Literals::Int(0))
+        };
+
+        Ok(Node::Exit(Box::new(code)))
+    }
+
+    fn parse_read_key(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        Ok(Node::ReadKey)
+    }
+
+    fn parse_input_int(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let prompt = match &self.current_token {
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_identifier() => Some(Box::new(self.parse_value("input_int")?)),
+            _ => None
         };
 
+        Ok(Node::InputInt { prompt })
+    }
+
+    fn parse_color(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value(&builtin)?;
+        let color = self.parse_value(&builtin)?;
+
+        if builtin == "colorl" {
+            return Ok(Node::ColorLog {
+                value: Box::new(value),
+                color: Box::new(color)
+            });
+        }
+
+        Ok(Node::Color {
+            value: Box::new(value),
+            color: Box::new(color)
+        })
+    }
+
+    fn parse_format(&mut self) -> ParserResult<Node> {
         self.advance();
 
+        let template = self.parse_value("format")?;
         let mut args: Vec<Box<Node>> = vec![];
 
         while let Some(token) = &self.current_token {
-            let foo = match token.r#type {
-                r#type if r#type.is_literal() => self.parse_literal()?,
-                r#type if r#type.is_identifier() => self.parse_identifier()?,
-                _ => break,
-            };
+            if !token.r#type.is_literal() &&
+               !token.r#type.is_identifier() &&
+               !token.r#type.is_fn_call() &&
+               !token.r#type.is_open_bracket() {
+                break;
+            }
 
-            args.push(Box::new(foo));
+            args.push(Box::new(self.parse_value("format")?));
         }
 
-        Ok(Node::FunctionCall {
-            identifier: Box::new(identifier),
-            args,
+        Ok(Node::Format {
+            template: Box::new(template),
+            args
         })
     }
 
-    // The only function that has comments because its kinda confusing
-    // Works as cd. you cd to the current dir the source will go to
-    // Basically thats it.
-    // Idk why even this exists but yeah.
-    fn parse_source(&mut self) -> ParserResult<Node> {
+    // Pairs of a field name and its value, like @record "name" "bob" "age" 3
+    fn parse_record(&mut self) -> ParserResult<Node> {
         self.advance();
 
-        let cwd = match env::current_dir() {
-            Ok(cwd) => cwd,
-            Err(_) => return Err(ParserError {
-                message: format!("Cannot get the current working directory"),
-                token: None
-            })
-        };
+        let mut fields: Vec<(Node, Node)> = vec![];
 
-        let source_path = match self.parse_literal() {
-            Ok(Node::Literal(Literals::String(source_path))) => source_path,
-            Err(err) => return Err(err),
-            _ => unreachable!(),
-        };
+        while let Some(token) = &self.current_token {
+            if !token.r#type.is_literal() &&
+               !token.r#type.is_identifier() &&
+               !token.r#type.is_fn_call() &&
+               !token.r#type.is_open_bracket() {
+                break;
+            }
 
-        let source_absolute_path = match Path::new(&cwd.join(&source_path)).canonicalize() {
-            Ok(file_path) => file_path,
-            Err(err) => return Err(ParserError {
-                message: format!("Failed to parse file path {:?}: {source_path}", err.to_string()),
-                token: None
-            })
-        };
+            let field = self.parse_value("record")?;
+            let value = self.parse_value("record")?;
 
-        if let Err(_) = env::set_current_dir(&source_absolute_path.parent().unwrap()) {
-            return Err(ParserError {
-                message: format!("Failed to change env directory to: {:?}", &source_absolute_path),
-                token: None
-            });
+            fields.push((field, value));
         }
 
-        let source = match fs::read_to_string(&source_absolute_path) {
-            Ok(contents) => contents,
-            Err(_) => {
-                return Err(ParserError {
-                    message: format!("Cannot find file {:?}", &source_absolute_path),
-                    token: None,
-                })
-            }
-        };
+        Ok(Node::Record(fields))
+    }
 
-        let tokens = match Lexer::new(source.as_str().chars()).lex() {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                return Err(ParserError {
-                    message: format!(
-                        r#"
-                        Error while lexing file: {:?}
-                        {color_red}[ERROR]{color_reset} -> Lexing Error: {}.
-                    "#,
-                        source_absolute_path, err.message
-                    ),
-                    token: None,
-                })
-            }
-        };
+    fn parse_parse_csv(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-        let ast = Parser::new(tokens.iter().cloned().into_iter()).parse()?;
+        let source = self.parse_value("parse_csv")?;
 
-        Ok(Node::Source {
-            file_name: source_path,
-            cwd: cwd.clone(),
-            ast
-        })
+        Ok(Node::ParseCsv(Box::new(source)))
     }
 
-    // Parse all expressions
-    fn parse_expr(&mut self) -> ParserResult<Node> {
-        if let Some(token) = &self.current_token {
-            // Check & Parse Array Access
-            if token.r#type.is_identifier() {
-                if let Some(condition) = self.peek() {
-                    if condition.r#type.is_open_bracket() {
-                        return self.parse_array_access();
-                    }
-                }
-            }
+    fn parse_to_csv(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-            // Check & Parse Condition
-            if token.r#type.is_literal() || token.r#type.is_identifier() {
-                if let Some(condition) = self.peek() {
-                    if condition.r#type.is_condition_op() {
-                        return self.parse_condition();
-                    }
-                }
-            }
+        let rows = self.parse_value("to_csv")?;
 
-            // Check & Parse Import/Fn_Call
-            if token.r#type.is_fn_call() {
-                return self.parse_function_call();
-            }
+        Ok(Node::ToCsv(Box::new(rows)))
+    }
 
-            // Check & Parse Literal
-            if token.r#type.is_literal() || token.r#type.is_open_bracket() {
-                return self.parse_literal();
-            }
+    fn parse_abs_path(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-            // Check & Parse Identifier
-            if token.r#type.is_identifier() {
-                return self.parse_identifier();
-            }
+        let path = self.parse_value("abs_path")?;
 
-            // Check & Parse Scope
-            if token.r#type.is_open_curly() {
-                return self.parse_scope();
-            }
+        Ok(Node::AbsPath(Box::new(path)))
+    }
+
+    fn parse_path_check(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let path = Box::new(self.parse_value(&builtin)?);
+
+        match builtin.as_str() {
+            "is_file"   => Ok(Node::IsFile(path)),
+            "is_dir"    => Ok(Node::IsDir(path)),
+            _           => Ok(Node::Exists(path))
         }
+    }
 
-        Err(ParserError {
-            message: format!("unexpected end of input while parsing expression"),
-            token: None,
+    fn parse_fixed(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("fixed")?;
+        let decimals = self.parse_value("fixed")?;
+
+        Ok(Node::Fixed {
+            value: Box::new(value),
+            decimals: Box::new(decimals)
         })
     }
 
-    fn parse_token(&mut self) -> ParserResult<Node> {
-        if let Some(token) = &self.current_token {
-            if token.r#type.is_statement() {
-                return self.parse_statement();
-            }
+    fn parse_clamp(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-            return self.parse_expr();
-        }
+        let value = self.parse_value("clamp")?;
+        let lo = self.parse_value("clamp")?;
+        let hi = self.parse_value("clamp")?;
 
-        Err(ParserError {
-            message: format!("Unhandled Token"),
-            token: Some(self.current_token.clone().unwrap()),
+        Ok(Node::Clamp {
+            value: Box::new(value),
+            lo: Box::new(lo),
+            hi: Box::new(hi)
         })
     }
 
-    pub fn parse(&mut self) -> ParserResult<Vec<Node>> {
-        let mut ast = Vec::new();
+    fn parse_round_to(&mut self) -> ParserResult<Node> {
+        self.advance();
 
-        while let Some(_) = &self.current_token {
-            let parsed_token = self.parse_token()?;
-            ast.push(parsed_token);
+        let value = self.parse_value("round_to")?;
+        let multiple = self.parse_value("round_to")?;
+
+        Ok(Node::RoundTo {
+            value: Box::new(value),
+            multiple: Box::new(multiple)
+        })
+    }
+
+    fn parse_gcd(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let left = Box::new(self.parse_value(&builtin)?);
+        let right = Box::new(self.parse_value(&builtin)?);
+
+        if builtin == "lcm" {
+            return Ok(Node::Lcm { left, right });
         }
 
-        Ok(ast)
+        Ok(Node::Gcd { left, right })
     }
 
-    fn advance(&mut self) {
-        self.current_token = self.tokens.next();
+    fn parse_starts_with(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let value = Box::new(self.parse_value(&builtin)?);
+        let affix = Box::new(self.parse_value(&builtin)?);
+
+        if builtin == "ends_with" {
+            return Ok(Node::EndsWith { value, suffix: affix });
+        }
+
+        Ok(Node::StartsWith { value, prefix: affix })
+    }
+
+    fn parse_min_by(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let function = Box::new(self.parse_value(&builtin)?);
+        let array = Box::new(self.parse_value(&builtin)?);
+
+        if builtin == "max_by" {
+            return Ok(Node::MaxBy { function, array });
+        }
+
+        Ok(Node::MinBy { function, array })
+    }
+
+    fn parse_find_from(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let array = self.parse_value("find_from")?;
+        let value = self.parse_value("find_from")?;
+        let start = self.parse_value("find_from")?;
+
+        Ok(Node::FindFrom {
+            array: Box::new(array),
+            value: Box::new(value),
+            start: Box::new(start)
+        })
+    }
+
+    fn parse_abs_diff(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let left = self.parse_value("abs_diff")?;
+        let right = self.parse_value("abs_diff")?;
+
+        Ok(Node::AbsDiff {
+            left: Box::new(left),
+            right: Box::new(right)
+        })
+    }
+
+    fn parse_count(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let haystack = self.parse_value("count")?;
+        let needle = self.parse_value("count")?;
+
+        Ok(Node::Count {
+            haystack: Box::new(haystack),
+            needle: Box::new(needle)
+        })
+    }
+
+    fn parse_replace(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let source = self.parse_value("replace")?;
+        let from = self.parse_value("replace")?;
+        let to = self.parse_value("replace")?;
+
+        Ok(Node::Replace {
+            source: Box::new(source),
+            from: Box::new(from),
+            to: Box::new(to)
+        })
+    }
+
+    fn parse_trim(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let value = Box::new(self.parse_value(&builtin)?);
+
+        match builtin.as_str() {
+            "trim_start"    => Ok(Node::TrimStart(value)),
+            "trim_end"      => Ok(Node::TrimEnd(value)),
+            _               => Ok(Node::Trim(value))
+        }
+    }
+
+    fn parse_zfill(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let number = self.parse_value("zfill")?;
+        let width = self.parse_value("zfill")?;
+
+        Ok(Node::ZFill {
+            number: Box::new(number),
+            width: Box::new(width)
+        })
+    }
+
+    fn parse_pad(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let value = Box::new(self.parse_value(&builtin)?);
+        let width = Box::new(self.parse_value(&builtin)?);
+
+        let fill = match &self.current_token {
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_identifier() => Some(Box::new(self.parse_value(&builtin)?)),
+            _ => None
+        };
+
+        if builtin == "pad_right" {
+            return Ok(Node::PadRight { value, width, fill });
+        }
+
+        Ok(Node::PadLeft { value, width, fill })
+    }
+
+    fn parse_flatten(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let array = self.parse_value("flatten")?;
+
+        let depth = match &self.current_token {
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_identifier() => Some(Box::new(self.parse_value("flatten")?)),
+            _ => None
+        };
+
+        Ok(Node::Flatten {
+            array: Box::new(array),
+            depth
+        })
+    }
+
+    fn parse_first_last(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let value = Box::new(self.parse_value(&builtin)?);
+
+        if builtin == "last" {
+            return Ok(Node::Last(value));
+        }
+
+        Ok(Node::First(value))
+    }
+
+    fn parse_take_drop(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let array = Box::new(self.parse_value(&builtin)?);
+        let count = Box::new(self.parse_value(&builtin)?);
+
+        if builtin == "drop" {
+            return Ok(Node::Drop { array, count });
+        }
+
+        Ok(Node::Take { array, count })
+    }
+
+    fn parse_deepcopy(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("deepcopy")?;
+
+        Ok(Node::DeepCopy(Box::new(value)))
+    }
+
+    fn parse_clone(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("clone")?;
+
+        Ok(Node::Clone(Box::new(value)))
+    }
+
+    fn parse_enumerate(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("enumerate")?;
+
+        Ok(Node::Enumerate(Box::new(value)))
+    }
+
+    fn parse_chars(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("chars")?;
+
+        Ok(Node::Chars(Box::new(value)))
+    }
+
+    fn parse_factorial(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("factorial")?;
+
+        Ok(Node::Factorial(Box::new(value)))
+    }
+
+    fn parse_is_prime(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("is_prime")?;
+
+        Ok(Node::IsPrime(Box::new(value)))
+    }
+
+    fn parse_lines(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("lines")?;
+
+        Ok(Node::Lines(Box::new(value)))
+    }
+
+    fn parse_choice(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let array = self.parse_value("choice")?;
+
+        Ok(Node::Choice(Box::new(array)))
+    }
+
+    fn parse_capitalize(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let value = Box::new(self.parse_value(&builtin)?);
+
+        if builtin == "title_case" {
+            return Ok(Node::TitleCase(value));
+        }
+
+        Ok(Node::Capitalize(value))
+    }
+
+    fn parse_empty(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("empty")?;
+
+        Ok(Node::Empty(Box::new(value)))
+    }
+
+    fn parse_memoize(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let function = self.parse_value("memoize")?;
+
+        Ok(Node::Memoize(Box::new(function)))
+    }
+
+    fn parse_bench(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("bench")?;
+
+        let count = match &self.current_token {
+            Some(token) if token.r#type.is_literal() ||
+                           token.r#type.is_identifier() => Some(Box::new(self.parse_value("bench")?)),
+            _ => None
+        };
+
+        Ok(Node::Bench {
+            value: Box::new(value),
+            count
+        })
+    }
+
+    fn parse_if_expr(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let cond = self.parse_condition()?;
+        let then_val = self.parse_value("if")?;
+        let else_val = self.parse_value("if")?;
+
+        Ok(Node::IfExpr {
+            cond: Box::new(cond),
+            then_val: Box::new(then_val),
+            else_val: Box::new(else_val)
+        })
+    }
+
+    fn parse_reduce(&mut self, builtin: String) -> ParserResult<Node> {
+        self.advance();
+
+        let array = Box::new(self.parse_value(&builtin)?);
+
+        match builtin.as_str() {
+            "product"   => Ok(Node::Product(array)),
+            "avg"       => Ok(Node::Avg(array)),
+            _           => Ok(Node::Sum(array))
+        }
+    }
+
+    fn parse_debug(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("debug")?;
+
+        Ok(Node::Debug(Box::new(value)))
+    }
+
+    fn parse_clear(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        Ok(Node::Clear)
+    }
+
+    fn parse_move_cursor(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let row = self.parse_value("move_cursor")?;
+        let col = self.parse_value("move_cursor")?;
+
+        Ok(Node::MoveCursor {
+            row: Box::new(row),
+            col: Box::new(col)
+        })
+    }
+
+    // Parses a single value passed to a builtin
+    fn parse_value(&mut self, builtin: &str) -> ParserResult<Node> {
+        match &self.current_token.clone() {
+            Some(token) => match token {
+                token if token.r#type.is_literal() ||
+                        token.r#type.is_open_bracket()  => self.parse_literal(),
+                token if token.r#type.is_fn_call()      => self.parse_function_call(),
+                token if token.r#type.is_open_paren()   => self.parse_group(),
+                token if token.r#type.is_identifier() &&
+                        self.peek().is_some() &&
+                        self.peek().unwrap()
+                            .r#type.is_open_bracket()   => self.parse_array_access(),
+                token if token.r#type.is_identifier()   => self.parse_identifier(),
+                _ => Err(ParserError {
+                    message: format!(
+                        "Expected a literal/identifier/function call on @{}, but found {:?}",
+                        builtin,
+                        token.r#type
+                    ),
+                    token: Some(token.clone()),
+                })
+            },
+            None => Err(ParserError {
+                message: format!("Unexpected end of input while parsing @{}", builtin),
+                token: None,
+            })
+        }
+    }
+
+    fn parse_scope(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let mut body: Vec<Box<Node>> = vec![];
+
+        while let Some(token) = &self.current_token {
+            if token.r#type.is_close_curly() {
+                self.advance();
+                break;
+            }
+
+            let parsed_token = self.parse_token()?;
+            body.push(Box::new(parsed_token));
+        }
+
+        Ok(Node::Scope { body })
+    }
+
+    fn parse_function_call(&mut self) -> ParserResult<Node> {
+        let line = self.current_token.as_ref().map(|token| token.line).unwrap_or_default();
+
+        let identifier = match &self.current_token {
+            Some(token) => {
+                if let Some(fn_call_name) = &token.value {
+                    if fn_call_name == "source" {
+                        return self.parse_source();
+                    }
+
+                    if fn_call_name == "math" {
+                        return self.parse_math_expr();
+                    }
+
+                    if fn_call_name == "rand" {
+                        return self.parse_rand();
+                    }
+
+                    if fn_call_name == "args" {
+                        return self.parse_args();
+                    }
+
+                    if fn_call_name == "arg" {
+                        return self.parse_arg();
+                    }
+
+                    if fn_call_name == "error" {
+                        return self.parse_raise();
+                    }
+
+                    if fn_call_name == "exit" {
+                        return self.parse_exit();
+                    }
+
+                    if fn_call_name == "read_key" {
+                        return self.parse_read_key();
+                    }
+
+                    if fn_call_name == "input_int" {
+                        return self.parse_input_int();
+                    }
+
+                    if fn_call_name == "color" || fn_call_name == "colorl" {
+                        return self.parse_color(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "format" {
+                        return self.parse_format();
+                    }
+
+                    if fn_call_name == "parse_csv" {
+                        return self.parse_parse_csv();
+                    }
+
+                    if fn_call_name == "to_csv" {
+                        return self.parse_to_csv();
+                    }
+
+                    if fn_call_name == "abs_path" {
+                        return self.parse_abs_path();
+                    }
+
+                    if fn_call_name == "fixed" {
+                        return self.parse_fixed();
+                    }
+
+                    if fn_call_name == "clamp" {
+                        return self.parse_clamp();
+                    }
+
+                    if fn_call_name == "round_to" {
+                        return self.parse_round_to();
+                    }
+
+                    if fn_call_name == "gcd" || fn_call_name == "lcm" {
+                        return self.parse_gcd(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "exists" || fn_call_name == "is_file" || fn_call_name == "is_dir" {
+                        return self.parse_path_check(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "starts_with" || fn_call_name == "ends_with" {
+                        return self.parse_starts_with(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "min_by" || fn_call_name == "max_by" {
+                        return self.parse_min_by(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "find_from" {
+                        return self.parse_find_from();
+                    }
+
+                    if fn_call_name == "abs_diff" {
+                        return self.parse_abs_diff();
+                    }
+
+                    if fn_call_name == "count" {
+                        return self.parse_count();
+                    }
+
+                    if fn_call_name == "replace" {
+                        return self.parse_replace();
+                    }
+
+                    if fn_call_name == "trim" || fn_call_name == "trim_start" || fn_call_name == "trim_end" {
+                        return self.parse_trim(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "zfill" {
+                        return self.parse_zfill();
+                    }
+
+                    if fn_call_name == "pad_left" || fn_call_name == "pad_right" {
+                        return self.parse_pad(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "flatten" {
+                        return self.parse_flatten();
+                    }
+
+                    if fn_call_name == "first" || fn_call_name == "last" {
+                        return self.parse_first_last(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "take" || fn_call_name == "drop" {
+                        return self.parse_take_drop(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "enumerate" {
+                        return self.parse_enumerate();
+                    }
+
+                    if fn_call_name == "chars" {
+                        return self.parse_chars();
+                    }
+
+                    if fn_call_name == "factorial" {
+                        return self.parse_factorial();
+                    }
+
+                    if fn_call_name == "is_prime" {
+                        return self.parse_is_prime();
+                    }
+
+                    if fn_call_name == "lines" {
+                        return self.parse_lines();
+                    }
+
+                    if fn_call_name == "choice" {
+                        return self.parse_choice();
+                    }
+
+                    if fn_call_name == "record" {
+                        return self.parse_record();
+                    }
+
+                    if fn_call_name == "capitalize" || fn_call_name == "title_case" {
+                        return self.parse_capitalize(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "empty" {
+                        return self.parse_empty();
+                    }
+
+                    if fn_call_name == "memoize" {
+                        return self.parse_memoize();
+                    }
+
+                    if fn_call_name == "bench" {
+                        return self.parse_bench();
+                    }
+
+                    if fn_call_name == "deepcopy" {
+                        return self.parse_deepcopy();
+                    }
+
+                    if fn_call_name == "clone" {
+                        return self.parse_clone();
+                    }
+
+                    if fn_call_name == "sum" || fn_call_name == "product" || fn_call_name == "avg" {
+                        return self.parse_reduce(fn_call_name.clone());
+                    }
+
+                    if fn_call_name == "if" {
+                        return self.parse_if_expr();
+                    }
+
+                    if fn_call_name == "debug" {
+                        return self.parse_debug();
+                    }
+
+                    if fn_call_name == "clear" {
+                        return self.parse_clear();
+                    }
+
+                    if fn_call_name == "move_cursor" {
+                        return self.parse_move_cursor();
+                    }
+                }
+
+                Node::Identifier(Symbol::intern(token.value.as_deref().unwrap_or_default()))
+            }
+            None => {
+                return Err(ParserError {
+                    message: format!("Unexpected end of input while parsing function call"),
+                    token: None,
+                })
+            }
+        };
+
+        self.advance();
+
+        // @handlers[0] calls the function stored in the array
+        let identifier = self.parse_indexes(identifier)?;
+
+        let mut args: Vec<Box<Node>> = vec![];
+
+        while let Some(token) = &self.current_token {
+            // A builtin or (group) on the next line is its own expression,
+            // so those only count as args when they're on the call's line
+            let same_line = token.line == line;
+
+            let arg = match token.r#type {
+                r#type if r#type.is_literal() => self.parse_literal()?,
+                r#type if r#type.is_identifier() => self.parse_array_access()?,
+                r#type if r#type.is_fn_call() &&
+                          same_line &&
+                          BUILTINS.contains(&token.value.as_deref().unwrap_or_default()) => self.parse_function_call()?,
+                r#type if r#type.is_open_paren() && same_line => self.parse_group()?,
+                _ => break,
+            };
+
+            args.push(Box::new(arg));
+        }
+
+        Ok(Node::FunctionCall {
+            identifier: Box::new(identifier),
+            args,
+        })
+    }
+
+    // A value wrapped in parentheses, like (@double 3), so a function
+    // call can be passed along without taking every arg after it.
+    // Groups can nest, the inner value is parsed like any other.
+    fn parse_group(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let value = self.parse_value("group")?;
+
+        match &self.current_token {
+            Some(token) if token.r#type.is_close_paren() => self.advance(),
+            Some(token) => return Err(ParserError {
+                message: format!("Expected close parenthesis, but found {:?}", token.r#type),
+                token: Some(token.clone()),
+            }),
+            None => return Err(ParserError {
+                message: format!("Unexpected end of input while parsing parentheses"),
+                token: None,
+            })
+        }
+
+        Ok(value)
+    }
+
+    // Only records the path, reading and running the file
+    // is left to the interpreter so parsing has no side effects.
+    fn parse_source(&mut self) -> ParserResult<Node> {
+        self.advance();
+
+        let file_name = match self.parse_literal()? {
+            Node::Literal(Literals::String(file_name)) => file_name,
+            literal => return Err(ParserError {
+                message: format!("Expected a string path on @source, but found {:?}", literal),
+                token: self.current_token.clone()
+            })
+        };
+
+        let alias = match &self.current_token {
+            Some(token) if token.r#type.is_identifier() &&
+                           token.value.as_deref() == Some("as") => {
+                self.advance();
+
+                match self.parse_identifier()? {
+                    Node::Identifier(alias) => Some(alias),
+                    _ => unreachable!()
+                }
+            },
+            _ => None
+        };
+
+        Ok(Node::Source { file_name, alias })
+    }
+
+    // Parse all expressions
+    fn parse_expr(&mut self) -> ParserResult<Node> {
+        if let Some(token) = &self.current_token {
+            // Check & Parse Array Access
+            if token.r#type.is_identifier() {
+                if let Some(condition) = self.peek() {
+                    if condition.r#type.is_open_bracket() {
+                        return self.parse_array_access();
+                    }
+                }
+            }
+
+            // Check & Parse Condition
+            if token.r#type.is_literal() || token.r#type.is_identifier() {
+                if let Some(condition) = self.peek() {
+                    if condition.r#type.is_condition_op() {
+                        return self.parse_condition();
+                    }
+                }
+            }
+
+            // Check & Parse Import/Fn_Call
+            if token.r#type.is_fn_call() {
+                return self.parse_function_call();
+            }
+
+            // Check & Parse Grouping
+            if token.r#type.is_open_paren() {
+                return self.parse_group();
+            }
+
+            // Check & Parse Literal
+            if token.r#type.is_literal() || token.r#type.is_open_bracket() {
+                return self.parse_literal();
+            }
+
+            // Check & Parse Identifier
+            if token.r#type.is_identifier() {
+                return self.parse_identifier();
+            }
+
+            // Check & Parse Scope
+            if token.r#type.is_open_curly() {
+                return self.parse_scope();
+            }
+        }
+
+        Err(ParserError {
+            message: format!("unexpected end of input while parsing expression"),
+            token: None,
+        })
+    }
+
+    fn parse_token(&mut self) -> ParserResult<Node> {
+        if let Some(token) = &self.current_token {
+            let (line, col) = (token.line, token.col);
+
+            let node = if token.r#type.is_statement() {
+                self.parse_statement()?
+            } else {
+                self.parse_expr()?
+            };
+
+            return Ok(Node::Statement { line, col, node: Box::new(node) });
+        }
+
+        Err(ParserError {
+            message: format!("Unhandled Token"),
+            token: Some(self.current_token.clone().unwrap()),
+        })
+    }
+
+    pub fn parse(&mut self) -> ParserResult<Vec<Node>> {
+        let mut ast = Vec::new();
+
+        while let Some(_) = &self.current_token {
+            let parsed_token = self.parse_token()?;
+            ast.push(parsed_token);
+        }
+
+        Ok(ast)
+    }
+
+    fn advance(&mut self) {
+        self.current_token = std::mem::replace(&mut self.next_token, self.tokens.next());
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.next_token.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(src: &str) -> ParserResult<Vec<Node>> {
+        let tokens = Lexer::new(src.chars()).lex().unwrap();
+        Parser::new(tokens.into_iter()).parse()
+    }
+
+    #[test]
+    fn empty_and_single_element_arrays_parse() {
+        assert!(parse("set a []").is_ok());
+        assert!(parse("set a [1]").is_ok());
+        assert!(parse("set a [[]]").is_ok());
+    }
+
+    #[test]
+    fn mixed_arrays_are_an_error_even_at_the_end_of_input() {
+        let err = parse("set a [1 \"x\"]").unwrap_err();
+        assert!(err.message.starts_with("Cannot have two or more types in array"), "{}", err.message);
+
+        let err = parse("set a [[1] [\"x\"]]\nlogl a").unwrap_err();
+        assert!(err.message.starts_with("Cannot have two or more types in array"), "{}", err.message);
+    }
+
+    #[test]
+    fn first_parse_error_comes_before_a_later_lexer_error() {
+        // Tokens are pulled lazily, so the lexer never reaches the bad string
+        // on line 3 before the parser fails on line 2
+        let err = crate::parse_source("logl 1\nset logl\nlogl \"unterminated").unwrap_err();
+
+        assert!(matches!(err, crate::AsplError::Parser(_)), "{:?}", err);
+        assert_eq!(err.position().map(|(line, _)| line), Some(2));
     }
 
-    fn peek(&self) -> Option<Token> {
-        self.tokens.clone().next()
+    #[test]
+    fn lexer_error_is_reported_when_it_cuts_the_input_short() {
+        let err = crate::parse_source("logl 1\nset x \"unterminated").unwrap_err();
+        assert!(matches!(err, crate::AsplError::Lexer(_)), "{:?}", err);
     }
 }
@@ -0,0 +1,74 @@
+use std::{collections::HashMap, fmt, sync::{Arc, Mutex, OnceLock}};
+
+// An identifier name interned to a small id, so envs hash and
+// compare a u32 instead of the whole name on every access
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Arc<str>, Symbol>,
+    names: Vec<Arc<str>>,
+}
+
+// Shared by the whole process, so a symbol made on one thread
+// names the same identifier on any other
+static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+fn interner() -> &'static Mutex<Interner> {
+    INTERNER.get_or_init(|| Mutex::new(Interner::default()))
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let mut interner = interner().lock().unwrap();
+
+        if let Some(symbol) = interner.ids.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(interner.names.len() as u32);
+        let name: Arc<str> = Arc::from(name);
+
+        interner.names.push(name.clone());
+        interner.ids.insert(name, symbol);
+
+        symbol
+    }
+
+    pub fn name(&self) -> Arc<str> {
+        interner().lock().unwrap().names[self.0 as usize].clone()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Shows the name rather than the id, so error messages and
+// debug output read the same as when names were strings
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", &*self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn symbols_name_the_same_identifier_across_threads() {
+        let symbol = Symbol::intern("shared_across_threads");
+
+        let (name, same) = thread::spawn(move || {
+            (symbol.name(), Symbol::intern("shared_across_threads"))
+        }).join().unwrap();
+
+        assert_eq!(&*name, "shared_across_threads");
+        assert!(symbol == same);
+    }
+}